@@ -0,0 +1,38 @@
+extern crate byteorder;
+#[macro_use]
+extern crate criterion;
+extern crate mtklogo;
+
+use byteorder::{BigEndian, LittleEndian};
+use criterion::{black_box, Criterion, Throughput};
+use mtklogo::utils::image;
+
+/// A full HD portrait frame, the size of most boot logos nowadays.
+const W: u32 = 1080;
+const H: u32 = 1920;
+
+/// Some RGBA raster with varying pixels, contents do not matter to the kernels.
+fn frame() -> Vec<u8> {
+    (0..W * H * 4).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect()
+}
+
+fn kernels(c: &mut Criterion) {
+    let rgba = frame();
+    let rgb565 = image::rgba_to_rgb565::<LittleEndian>(&rgba, W, H).unwrap();
+    let mut group = c.benchmark_group("1080x1920");
+    group.throughput(Throughput::Elements((W * H) as u64));
+    group.bench_function("rgba_to_bgra", |b| b.iter(
+        || image::rgba_to_bgra::<BigEndian>(black_box(&rgba), W, H).unwrap()));
+    group.bench_function("bgra_to_rgba", |b| b.iter(
+        || image::bgra_to_rgba::<LittleEndian>(black_box(&rgba), W, H).unwrap()));
+    group.bench_function("u32be_to_u32le", |b| b.iter(
+        || image::u32be_to_u32le(black_box(&rgba), (W * H) as usize).unwrap()));
+    group.bench_function("rgba_to_rgb565", |b| b.iter(
+        || image::rgba_to_rgb565::<LittleEndian>(black_box(&rgba), W, H).unwrap()));
+    group.bench_function("rgb565_to_rgba", |b| b.iter(
+        || image::rgb565_to_rgba::<LittleEndian>(black_box(&rgb565), W, H).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, kernels);
+criterion_main!(benches);
@@ -0,0 +1,68 @@
+extern crate gif;
+
+use self::gif::{DisposalMethod, Encoder, Frame, Repeat};
+use std::cmp;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Error as IOError, ErrorKind, Result};
+use std::path::PathBuf;
+use super::{cmd, data1, data2, data3, decode_slot, emit, emphasize1, emphasize2, Slots};
+use super::mtklogo::{ColorMode, LogoImage};
+use super::super::config::Profile;
+
+pub fn run_animate(profile: Profile, slots: Slots, delay: u32, flip: bool,
+                   path: PathBuf, output: PathBuf) -> Result<()> {
+    let mtk_color_model = ColorMode::by_name(&profile.color_model)?;
+    say!("{} slots {} of file {} with profile {}, color mode {}, {}ms per frame, to {}.",
+         cmd("animate"),
+         slots,
+         emphasize1(path.display()),
+         data1(&profile.name),
+         data2(mtk_color_model),
+         data1(delay),
+         emphasize1(output.display()));
+
+    // reads whole image in memory.
+    let mut reader = BufReader::new(File::open(path)?);
+    let image = LogoImage::read(&mut reader)?;
+    // decodes all frames, an animation with a hole makes no sense so we fail.
+    let slots = slots.list(image.blobs.len())?;
+    let mut frames = Vec::with_capacity(slots.len());
+    for id in slots.iter() {
+        let blob = &image.blobs[*id];
+        // the profile's entry for this slot prevails, then its size tells the format.
+        let color_mode = profile.slot_color_mode(*id)?;
+        let decoded = decode_slot(blob, color_mode, |sz| profile.slot_format(*id, sz, flip))?;
+        say!("frame {} is slot {}, a {}x{} {} image.",
+             data1(frames.len()), data1(id), data3(decoded.w), data3(decoded.h),
+             emphasize2(color_mode));
+        emit(json!({"event": "frame", "frame": frames.len(), "slot": id, "width": decoded.w, "height": decoded.h,
+                    "color_mode": color_mode.to_string()}));
+        frames.push(decoded);
+    }
+    // frames may not be the same size, the screen must fit them all.
+    let screen_w = frames.iter().map(|f| f.w).max().unwrap_or(0);
+    let screen_h = frames.iter().map(|f| f.h).max().unwrap_or(0);
+    if screen_w > u16::MAX as u32 || screen_h > u16::MAX as u32 {
+        return Err(IOError::new(ErrorKind::InvalidData,
+                                format!("{}x{} is too large for a GIF animation", screen_w, screen_h)));
+    }
+
+    let writer = BufWriter::new(File::create(&output)?);
+    let mut encoder = Encoder::new(writer, screen_w as u16, screen_h as u16, &[]).map_err(gif_error)?;
+    encoder.set_repeat(Repeat::Infinite).map_err(gif_error)?;
+    for mut decoded in frames.into_iter() {
+        // GIF quantizes colors (256 colors per frame), speed 10 is a fair compromise.
+        let mut frame = Frame::from_rgba_speed(decoded.w as u16, decoded.h as u16, &mut decoded.rgba, 10);
+        // GIF delays are in 1/100th of a second.
+        frame.delay = cmp::min(delay / 10, u16::MAX as u32) as u16;
+        frame.dispose = DisposalMethod::Background;
+        encoder.write_frame(&frame).map_err(gif_error)?;
+    }
+    say!("successfully animated {} slots to {}", data1(slots.len()), emphasize1(output.display()));
+    emit(json!({"event": "written", "path": output.display().to_string(), "frames": slots.len()}));
+    Ok(())
+}
+
+fn gif_error(e: gif::EncodingError) -> IOError {
+    IOError::new(ErrorKind::InvalidData, e.to_string())
+}
@@ -0,0 +1,115 @@
+use serde_json;
+use serde_yaml;
+use std::fs;
+use std::io::{Error as IOError, ErrorKind, Result};
+use std::path::PathBuf;
+use super::{cmd, data1, data2, data3, document, emit, emphasize1, emphasize2, err, warn};
+use super::super::config::{check_file, Config, Profile};
+
+/// `builtin` tells whether the built-in profiles come after the files.
+pub fn run_config_path(paths: Vec<PathBuf>, builtin: bool) -> Result<()> {
+    say!("{} {} configuration files, from the highest priority.", cmd("config path"), data1(paths.len()));
+    for path in paths.iter() {
+        result!("{}", emphasize1(path.display()));
+        emit(json!({"event": "config", "path": path.display().to_string()}));
+    }
+    if builtin {
+        result!("{}", data2("built-in profiles"));
+        emit(json!({"event": "config", "builtin": true}));
+    }
+    Ok(())
+}
+
+/// Writes the built-in profiles to a file, the user's configuration by default.
+pub fn run_config_init(output: Option<PathBuf>, force: bool) -> Result<()> {
+    let output = match output {
+        Some(output) => output,
+        None => Config::user_config_path()?,
+    };
+    say!("{} to {}.", cmd("config init"), emphasize1(output.display()));
+    if output.exists() && !force {
+        return Err(IOError::new(ErrorKind::AlreadyExists,
+                                format!("{} already exists, use --force to overwrite it", output.display())));
+    }
+    if let Some(dir) = output.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    Config::write_builtin(&output)?;
+    let count = Config::builtin()?.profiles.len();
+    say!("{} built-in profiles written.", data1(count));
+    emit(json!({"event": "written", "path": output.display().to_string(), "profiles": count}));
+    Ok(())
+}
+
+pub fn run_config_list(config: Config) -> Result<()> {
+    say!("{} {} profiles.", cmd("config list"), data1(config.profiles.len()));
+    for declared in config.profiles.iter() {
+        let aliases = declared.alias.as_ref().map_or(String::new(), |aliases| format!("alias {}", aliases.join(", ")));
+        // inherited formats count.
+        match config.profile(&declared.name) {
+            Ok(profile) => {
+                result!("{} {} {} formats {}",
+                     data1(format!("{:<24}", profile.name)),
+                     emphasize2(format!("{:<10}", profile.color_model)),
+                     data3(format!("{:>4}", profile.formats.len())),
+                     data2(aliases));
+                emit(json!({"event": "profile", "name": profile.name, "color_model": profile.color_model,
+                            "formats": profile.formats.len(), "alias": declared.alias}));
+            }
+            Err(e) => {
+                result!("{} {}", data1(format!("{:<24}", declared.name)), warn(&e));
+                emit(json!({"event": "profile", "name": declared.name, "error": e.to_string()}));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The profile is a document on stdout, prose goes to stderr.
+pub fn run_config_show(profile: Profile, json: bool) -> Result<()> {
+    say!("{} profile {}.", cmd("config show"), data1(&profile.name));
+    let text = if json {
+        serde_json::to_string_pretty(&profile).map_err(|e| IOError::new(ErrorKind::InvalidData, e))?
+    } else {
+        serde_yaml::to_string(&profile).map_err(|e| IOError::new(ErrorKind::InvalidData, e))?
+    };
+    document(text.trim_end());
+    let value = serde_json::to_value(&profile).map_err(|e| IOError::new(ErrorKind::InvalidData, e))?;
+    emit(json!({"event": "profile", "profile": value}));
+    Ok(())
+}
+
+/// `builtin` tells whether profiles may extend the built-in ones.
+pub fn run_config_check(paths: Vec<PathBuf>, builtin: bool) -> Result<()> {
+    say!("{} {} configuration files.", cmd("config check"), data1(paths.len()));
+    // profiles may extend the profiles of other files, which are resolved in the merged configuration.
+    let mut configs: Vec<Config> = paths.iter().filter_map(|path| Config::from_file(path).ok()).collect();
+    if builtin {
+        configs.push(Config::builtin()?);
+    }
+    let merged = Config::merge(configs).unwrap_or(Config { version: String::new(), profiles: Vec::new() });
+    let mut count = 0;
+    for path in paths.iter() {
+        let problems = check_file(path, &merged)?;
+        if problems.is_empty() {
+            result!("{} is fine.", emphasize1(path.display()));
+        } else {
+            result!("{} has {} problems:", emphasize1(path.display()), warn(problems.len()));
+            for problem in problems.iter() {
+                let location = match problem.line {
+                    Some(line) => format!("{}:{}", path.display(), line),
+                    None => format!("{}", path.display()),
+                };
+                result!("  {}: {}", data1(location), err(problem));
+                emit(json!({"event": "problem", "path": path.display().to_string(), "line": problem.line,
+                            "profile": problem.profile, "message": problem.message}));
+            }
+        }
+        emit(json!({"event": "checked", "path": path.display().to_string(), "problems": problems.len()}));
+        count += problems.len();
+    }
+    if count > 0 {
+        return Err(IOError::new(ErrorKind::InvalidData, format!("configuration has {} problems", count)));
+    }
+    Ok(())
+}
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::fmt;
 use std::fmt::Display;
 use std::io::Result;
-use super::{cmd, emphasize1, data1, data2, data3};
+use super::{cmd, emit, emphasize1, data1, data2, data3};
 use super::mtklogo::ColorMode;
 
 #[derive(Clone)]
@@ -92,35 +92,51 @@ impl Display for Factor {
     }
 }
 
+/// Every width made of the available prime factors, with the factors making it, as `guess` tells them.
+fn explore(available_factors: &[Factor], a_factors: Vec<Factor>, found: &mut Vec<(Vec<Factor>, usize)>) {
+    let sz = available_factors.len();
+    // Takes a factor in the available factor bag.
+    let mut next_available = available_factors.to_vec();
+    for _ in 0..sz {
+        let current_factors = next_available.remove(0);
+        for pow in current_factors.divisors() {
+            // Puts it in the possible factors for "a".
+            let mut next_a = a_factors.clone();
+            next_a.push(pow);
+            // The first member of the solution is the product of all a 's.
+            let product = |mut prod: usize, x: &Factor| {
+                prod *= x.value();
+                prod
+            };
+            let www = next_a.iter().fold(1, product);
+            found.push((next_a.clone(), www));
+            // continues
+            explore(&next_available, next_a, found);
+        }
+    }
+}
+
+/// The dimensions `guess` would tell for `n` pixels, as (width, height).
+pub fn candidates(n: usize) -> Vec<(usize, usize)> {
+    let mut found = Vec::new();
+    explore(&Factor::decompose(n), Vec::new(), &mut found);
+    found.into_iter().map(|(_, www)| (www, n / www)).collect()
+}
 
 pub fn run_guess(size: usize) -> Result<()> {
-    println!("{} possible dimensions of a {} bytes blob",
-             cmd("guess"),
-             data1(size));
-    fn explore(available_factors: &Vec<Factor>, a_factors: Vec<Factor>, n: usize) {
-        let sz = available_factors.len();
-        // Takes a factor in the available factor bag.
-        let mut next_available = available_factors.clone();
-        for _ in 0..sz {
-            let current_factors = next_available.remove(0);
-            for pow in current_factors.divisors() {
-                // Puts it in the possible factors for "a".
-                let mut next_a = a_factors.clone();
-                next_a.push(pow);
-                // The first member of the solution is the product of all a 's.
-                let product = |mut prod: usize, x: &Factor| {
-                    prod *= x.value();
-                    prod
-                };
-                let www = next_a.iter().fold(1 as usize, product);
-                // This is the second member of the solution.
-                let hhh = n / www;
-                println!("It could be {} x {}. Because {} = ({}) * {}.",
-                         data3(www), data3(hhh),
-                         data2(n), Factors { factors: &next_a }, data1(hhh));
-                // continues
-                explore(&next_available, next_a, n);
-            }
+    say!("{} possible dimensions of a {} bytes blob",
+         cmd("guess"),
+         data1(size));
+    fn report(factors: &[Factor], n: usize) {
+        let mut found = Vec::new();
+        explore(factors, Vec::new(), &mut found);
+        for (a_factors, www) in found.iter() {
+            // This is the second member of the solution.
+            let hhh = n / www;
+            result!("It could be {} x {}. Because {} = ({}) * {}.",
+                 data3(www), data3(hhh),
+                 data2(n), Factors { factors: a_factors }, data1(hhh));
+            emit(json!({"event": "dimensions", "pixels": n, "width": www, "height": hhh}));
         }
     }
 
@@ -135,10 +151,12 @@ pub fn run_guess(size: usize) -> Result<()> {
         let factors = Factor::decompose(sz);
         let o:Vec<String> = modes.iter().map(|m| format!("{}", emphasize1(m))).collect();
         let colored_list = o.join(",");
-        println!("if {} bytes per pixel (modes: {}), {} bytes is {} pixels and has following divisors: {}.",
-                 data1(*bpp), colored_list, data3(size), data2(sz), Factors { factors: &factors });
+        result!("if {} bytes per pixel (modes: {}), {} bytes is {} pixels and has following divisors: {}.",
+             data1(*bpp), colored_list, data3(size), data2(sz), Factors { factors: &factors });
+        let names: Vec<String> = modes.iter().map(|m| m.to_string()).collect();
+        emit(json!({"event": "pixels", "bytes_per_pixel": bpp, "modes": names, "pixels": sz}));
         // explores possible arrangements
-        explore(&factors, Vec::new(), sz);
+        report(&factors, sz);
     }
     Ok(())
 }
@@ -0,0 +1,178 @@
+use std::fs;
+use std::io::{Error as IOError, ErrorKind, Result};
+use std::path::PathBuf;
+use super::{cmd, data1, data2, data3, emit, emphasize1, warn};
+use super::learn::save_profile;
+use super::mtklogo::ColorMode;
+use super::super::config::{Format, Profile};
+
+/// The tools whose device tables can be imported.
+pub const IMPORT_TOOLS: &[&str] = &["mtkimg", "mtk-tools"];
+
+/// A dimension of a device table, with its name when the table gives one.
+struct Dimension {
+    w: u32,
+    h: u32,
+    name: Option<String>,
+}
+
+/// Writes a new profile with the dimensions listed by another MTK logo tool.
+pub fn run_import(tool: &str, path: PathBuf, mode: &ColorMode, name: String, output: Option<PathBuf>) -> Result<()> {
+    say!("{} profile {} from {} table {}, color mode {}.",
+         cmd("profile import"),
+         data1(&name),
+         data2(tool),
+         emphasize1(path.display()),
+         data2(mode));
+    let text = fs::read_to_string(&path)?;
+    let dimensions = match tool {
+        "mtkimg" => mtkimg_dimensions(&text),
+        "mtk-tools" => mtk_tools_dimensions(&text),
+        _ => return Err(IOError::new(ErrorKind::InvalidInput, format!("unknown tool '{}'", tool))),
+    };
+    // a pixel count tells a single format.
+    let mut formats: Vec<Format> = Vec::new();
+    for d in dimensions {
+        if d.w == 0 || d.h == 0 {
+            say!("{} {}x{}: zero dimension.", warn("Skipping"), d.w, d.h);
+            continue;
+        }
+        match formats.iter().find(|f| f.pixels() == d.w as u64 * d.h as u64) {
+            Some(f) if (f.w, f.h) == (d.w, d.h) => (),
+            Some(f) => say!("{} {}x{}: same pixel count as {}x{}.", warn("Skipping"), d.w, d.h, f.w, f.h),
+            None => formats.push(Format { w: d.w, h: d.h, t: d.name, transform: None }),
+        }
+    }
+    if formats.is_empty() {
+        return Err(IOError::new(ErrorKind::InvalidData, format!("no dimension found in {} table", tool)));
+    }
+    let count = formats.len();
+    let profile = Profile {
+        name: name.clone(),
+        extends: None,
+        color_model: mode.to_string(),
+        alias: None,
+        transform: None,
+        level: None,
+        strategy: None,
+        slots: None,
+        formats,
+        remove_formats: None,
+        fingerprint: None,
+    };
+    let output = save_profile(profile, output)?;
+    say!("profile {} ({} formats) written to {}.", data1(&name), data3(count), emphasize1(output.display()));
+    emit(json!({"event": "written", "path": output.display().to_string(), "profile": name, "formats": count}));
+    Ok(())
+}
+
+/// mtkimg lists its resolutions as `{ "QVGA", 240, 320 }` entries, name, width then height,
+/// in the initializer of an array named after them: `resolution_t resolutions[] = { ... };`.
+fn mtkimg_dimensions(text: &str) -> Vec<Dimension> {
+    let text: Vec<&str> = text.lines().map(|line| line.split("//").next().unwrap_or("")).collect();
+    let text = text.join("\n");
+    // the declaration names an array, then its initializer follows.
+    let declaration = text.match_indices("resolution")
+        .map(|(start, _)| &text[start..])
+        .find(|rest| rest.contains('=') && rest.split('=').next()
+            .is_some_and(|name| name.contains('[') && !name.contains([';', '{', '}', '('])));
+    let list = match declaration {
+        Some(declaration) => &declaration[declaration.find('=').unwrap_or(0) + 1..],
+        None => return Vec::new(),
+    };
+    let list = list.split("};").next().unwrap_or("");
+    list.split('{').skip(1)
+        .filter_map(|entry| entry.split('}').next())
+        .filter_map(|entry| {
+            let fields: Vec<&str> = entry.split(',').map(|field| field.trim()).filter(|field| !field.is_empty()).collect();
+            let (name, w, h) = match fields.as_slice() {
+                [name, w, h] if name.starts_with('"') => (Some(name.trim_matches('"').to_string()), w, h),
+                [w, h] => (None, w, h),
+                _ => return None,
+            };
+            Some(Dimension { w: w.parse().ok()?, h: h.parse().ok()?, name })
+        })
+        .collect()
+}
+
+/// mtk-tools' unpack script lists its resolutions as `my @resolution = ([240,320], [480,854], ...);`.
+fn mtk_tools_dimensions(text: &str) -> Vec<Dimension> {
+    let text: Vec<&str> = text.lines().map(|line| line.split('#').next().unwrap_or("")).collect();
+    let text = text.join("\n");
+    let list = match text.find("@resolution") {
+        Some(start) => &text[start..],
+        None => return Vec::new(),
+    };
+    let list = list.split(");").next().unwrap_or("");
+    list.split('[').skip(1)
+        .filter_map(|pair| pair.split(']').next())
+        .filter_map(|pair| {
+            let fields: Vec<&str> = pair.split(',').map(|field| field.trim()).collect();
+            match fields.as_slice() {
+                [w, h] => Some(Dimension { w: w.parse().ok()?, h: h.parse().ok()?, name: None }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn test_tuples(dimensions: Vec<Dimension>) -> Vec<(u32, u32, Option<String>)> {
+    dimensions.into_iter().map(|d| (d.w, d.h, d.name)).collect()
+}
+
+#[test]
+fn test_mtkimg_dimensions() {
+    let table = r#"
+#include "mtkimg.h"
+
+typedef struct { const char *name; int width; int height; } resolution_t;
+static const int version[] = { 0, 1 };
+static const block_t blocks[] = { { 512, 1024 }, { "header", 0x200, 4 } };
+
+// logo resolutions, { name, width, height }
+static const resolution_t resolutions[] = {
+	{ "QVGA", 240, 320 },
+	{ "WVGA", 480, 800 },  // { "HVGA", 320, 480 },
+	{"FWVGA",480,854},
+	/* qHD */ { "qHD", 540, 960 },
+	{ 600, 1024 },
+	{ "HD720", 720, 1280, },
+	{ "FHD", 1080, 1920 },
+};
+
+static const point_t origin = { 16, 32 };
+int main(int argc, char **argv) { return 0; }
+"#;
+    let name = |name: &str| Some(name.to_string());
+    assert_eq!(test_tuples(mtkimg_dimensions(table)), vec![
+        (240, 320, name("QVGA")),
+        (480, 800, name("WVGA")),
+        (480, 854, name("FWVGA")),
+        (540, 960, name("qHD")),
+        (600, 1024, None),
+        (720, 1280, name("HD720")),
+        (1080, 1920, name("FHD")),
+    ]);
+    // other initializers are not resolutions.
+    assert!(mtkimg_dimensions("static const int sizes[] = { 240, 320 };\nint main() { return 0; }").is_empty());
+}
+
+#[test]
+fn test_mtk_tools_dimensions() {
+    let script = r#"#!/usr/bin/perl
+# unpack-MTK.pl - unpacks MTK boot, recovery and logo images
+use strict;
+use warnings;
+
+# resolutions of the logo images, [ width, height ]
+my @resolution = ([360,640], [480,800], [480,854],
+	# [320,480], not used anymore
+	[540,960], [ 720 , 1280 ], [1080,1920],);
+
+my @offsets = ([0,1]);
+"#;
+    let dimensions: Vec<(u32, u32)> = mtk_tools_dimensions(script).into_iter().map(|d| (d.w, d.h)).collect();
+    assert_eq!(dimensions, vec![(360, 640), (480, 800), (480, 854), (540, 960), (720, 1280), (1080, 1920)]);
+    assert!(mtk_tools_dimensions("my @offsets = ([0,1]);").is_empty());
+}
@@ -0,0 +1,80 @@
+#[cfg(feature = "parallel")]
+extern crate rayon;
+
+#[cfg(feature = "parallel")]
+use self::rayon::prelude::*;
+use serde_json::Value;
+use std::io::Result;
+#[cfg(feature = "parallel")]
+use std::io::Error as IOError;
+use super::{emit, result, say};
+
+/// What a slot job has to say. Jobs may run in any order, so they do not print:
+/// messages are printed once all jobs are done, in the order of the slots.
+#[derive(Default)]
+pub struct Log {
+    entries: Vec<Entry>,
+}
+
+/// Prose, a result as text, or a result of `--message-format json`.
+enum Entry {
+    Line(String),
+    Result(String),
+    Record(Value),
+}
+
+impl Log {
+    pub fn say(&mut self, line: String) {
+        self.entries.push(Entry::Line(line));
+    }
+
+    pub fn result(&mut self, line: String) {
+        self.entries.push(Entry::Result(line));
+    }
+
+    pub fn emit(&mut self, record: Value) {
+        self.entries.push(Entry::Record(record));
+    }
+
+    pub fn print(self) {
+        for entry in self.entries {
+            match entry {
+                Entry::Line(line) => say(line),
+                Entry::Result(line) => result(line),
+                Entry::Record(record) => emit(record),
+            }
+        }
+    }
+}
+
+/// Runs a job for each item, on `jobs` threads (0 means as many as cores).
+/// Results come back in the order of the items, whatever the order jobs ran in.
+#[cfg(feature = "parallel")]
+pub fn run_jobs<T, R, F>(items: &[T], jobs: usize, job: F) -> Result<Vec<(Log, R)>>
+    where T: Sync, R: Send, F: Fn(&T, &mut Log) -> R + Sync {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .map_err(|e| IOError::other(format!("cannot start {} jobs: {}", jobs, e)))?;
+    detail!("running {} jobs.", pool.current_num_threads());
+    Ok(pool.install(|| items.par_iter()
+        .map(|item| {
+            let mut log = Log::default();
+            let result = job(item, &mut log);
+            (log, result)
+        })
+        .collect()))
+}
+
+/// Runs a job for each item, one after the other: this program was built without the 'parallel' feature.
+#[cfg(not(feature = "parallel"))]
+pub fn run_jobs<T, R, F>(items: &[T], _jobs: usize, job: F) -> Result<Vec<(Log, R)>>
+    where F: Fn(&T, &mut Log) -> R {
+    Ok(items.iter()
+        .map(|item| {
+            let mut log = Log::default();
+            let result = job(item, &mut log);
+            (log, result)
+        })
+        .collect())
+}
@@ -0,0 +1,220 @@
+use serde_yaml;
+use std::fs::{self, File};
+use std::io::{BufReader, Error as IOError, ErrorKind, Result, Write};
+use std::path::{Path, PathBuf};
+use super::{cmd, data1, data2, data3, emit, emphasize1, emphasize2, guess, warn};
+use super::mtklogo::{ColorMode, ContentType, FileInfo, LogoImage};
+use super::mtklogo::utils::{codec, encoding, formats::ImageFormat};
+use super::super::config::{Config, Fingerprint, Format, Profile, Slot, SlotIndex};
+
+/// The dimensions of a slot, as learned.
+struct Learned {
+    id: usize,
+    w: u32,
+    h: u32,
+    mode: ColorMode,
+    /// Neither the width nor a known format told them.
+    guessed: bool,
+}
+
+/// A slot split at `--width` is believed as long as it is no flatter than this, e.g. 720x90.
+const MAX_ASPECT: u32 = 8;
+
+/// Learns a profile from a logo image: a format of the known profiles of the same color mode tells
+/// the dimensions of a slot, then it is as wide as `width` whenever it can be, otherwise they are guessed.
+pub fn run_learn(path: PathBuf, width: u32, mode: &ColorMode, name: String, known: Option<Config>,
+                 output: Option<PathBuf>) -> Result<()> {
+    say!("{} profile {} from file {}, width {}, color mode {}.",
+         cmd("profile learn"),
+         data1(&name),
+         emphasize1(path.display()),
+         data1(width),
+         data2(mode));
+    let mut reader = BufReader::new(File::open(path)?);
+    let image = LogoImage::read(&mut reader)?;
+    let inflater = codec::inflater()?;
+    let bpp = mode.bytes_per_pixel();
+    // formats of the known profiles which store pixels as this device does.
+    let known_formats: Vec<Format> = known.iter()
+        .flat_map(|config| config.profiles.iter().filter_map(move |declared| config.profile(&declared.name).ok()))
+        .filter(|profile| ColorMode::by_name(&profile.color_model).ok() == Some(mode))
+        .flat_map(|profile| profile.formats)
+        .collect();
+    let mut learned = Vec::with_capacity(image.blobs.len());
+    for (id, blob) in image.blobs.iter().enumerate() {
+        let (encoding, inflated) = encoding::decode(blob, inflater.as_ref());
+        let size = inflated.len() as u32;
+        let pixels = size / bpp;
+        if pixels == 0 || pixels * bpp != size {
+            say!("{} slot {}: {} bytes are not whole {} pixels.", warn("Skipping"), data1(id), data2(size), mode);
+            continue;
+        }
+        let (w, h, guessed) = dimensions(pixels, width, &known_formats);
+        let note = if guessed { format!(", {}", warn("guessed, please check it with explore")) } else { String::new() };
+        say!("slot {} is {} bytes of {}: {}x{}{}.", data1(id), data2(size), data2(encoding), data3(w), data3(h), note);
+        emit(json!({"event": "slot", "slot": id, "bytes": size, "encoding": encoding.to_string(),
+                    "width": w, "height": h, "guessed": guessed}));
+        learned.push(Learned { id, w, h, mode: mode.clone(), guessed });
+    }
+    // tells this device's logo images, without --profile.
+    let fingerprint = Fingerprint {
+        slots: Some(image.blobs.len()),
+        sizes: None,
+        encoding: None,
+        block_size: Some(image.table.block_size),
+    };
+    write_profile(name, learned, Some(fingerprint), output)
+}
+
+/// Learns a profile from the images left in an explore directory, once the wrong guesses are deleted.
+pub fn run_learn_from_dir(dir: PathBuf, name: String, output: Option<PathBuf>) -> Result<()> {
+    say!("{} profile {} from the images of directory {}.",
+         cmd("profile learn"),
+         data1(&name),
+         emphasize1(dir.display()));
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_>>()?;
+    paths.sort();
+    let mut learned: Vec<Learned> = Vec::with_capacity(paths.len());
+    for path in paths.iter() {
+        // explore's files are named after unpack's.
+        let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+        let (id, mode) = match FileInfo::from_name(file_name.trim_start_matches("explore_")) {
+            Ok(FileInfo { id, content_type: ContentType::Image(mode, ImageFormat::Png), .. }) => (id, mode),
+            _ => continue,
+        };
+        let (_, w, h) = ImageFormat::Png.read_rgba(BufReader::new(File::open(path)?))?;
+        if learned.iter().any(|l| l.id == id) {
+            say!("{} {}: slot {} is already learned.", warn("Skipping"), emphasize1(path.display()), data1(id));
+            continue;
+        }
+        say!("slot {} is {}x{} {}.", data1(id), data3(w), data3(h), emphasize2(&mode));
+        emit(json!({"event": "slot", "slot": id, "width": w, "height": h, "color_mode": mode.to_string(),
+                    "file": path.display().to_string()}));
+        learned.push(Learned { id, w, h, mode, guessed: false });
+    }
+    learned.sort_by_key(|l| l.id);
+    write_profile(name, learned, None, output)
+}
+
+/// The dimensions of a slot of `pixels`, and whether they are guessed.
+fn dimensions(pixels: u32, width: u32, known_formats: &[Format]) -> (u32, u32, bool) {
+    // a known format as wide as the device's first, then any.
+    let known_format = known_formats.iter().find(|f| f.pixels() == pixels as u64 && f.w == width)
+        .or_else(|| known_formats.iter().find(|f| f.pixels() == pixels as u64));
+    if let Some(format) = known_format {
+        (format.w, format.h, false)
+    } else if pixels.is_multiple_of(width) && (pixels / width).saturating_mul(MAX_ASPECT) >= width {
+        (width, pixels / width, false)
+    } else {
+        let (w, h) = squarest(pixels);
+        (w, h, true)
+    }
+}
+
+/// The squarest of the dimensions `guess` tells for a pixel count, wider than tall.
+fn squarest(pixels: u32) -> (u32, u32) {
+    guess::candidates(pixels as usize).into_iter()
+        .filter(|&(w, h)| w >= h)
+        .min_by_key(|&(w, h)| w - h)
+        .map_or((pixels, 1), |(w, h)| (w as u32, h as u32))
+}
+
+/// `fingerprint` tells the logo image, when the profile is learned from one.
+fn write_profile(name: String, learned: Vec<Learned>, fingerprint: Option<Fingerprint>, output: Option<PathBuf>) -> Result<()> {
+    if learned.is_empty() {
+        return Err(IOError::new(ErrorKind::InvalidData, "no slot could be learned"));
+    }
+    // the most frequent color mode is the profile's.
+    let color_mode = learned.iter()
+        .max_by_key(|l| learned.iter().filter(|other| other.mode == l.mode).count())
+        .map(|l| l.mode.clone())
+        .unwrap_or_else(|| learned[0].mode.clone());
+    // consecutive slots of the same kind make a range.
+    let mut slots: Vec<(usize, usize, &Learned)> = Vec::new();
+    for l in learned.iter() {
+        match slots.last_mut() {
+            Some((_, last, first)) if *last + 1 == l.id && (first.w, first.h, &first.mode) == (l.w, l.h, &l.mode) =>
+                *last = l.id,
+            _ => slots.push((l.id, l.id, l)),
+        }
+    }
+    let slots = slots.into_iter().map(|(first, last, l)| Slot {
+        index: if first == last { SlotIndex::One(first) } else { SlotIndex::Range(format!("{}..{}", first, last)) },
+        name: None,
+        w: l.w,
+        h: l.h,
+        color_model: if l.mode == color_mode { None } else { Some(l.mode.to_string()) },
+        transform: None,
+    }).collect();
+    // formats size the slots of other images of the device, a pixel count tells a single one.
+    let mut formats: Vec<Format> = Vec::new();
+    for l in learned.iter().filter(|l| l.mode == color_mode) {
+        if !formats.iter().any(|f| f.pixels() == l.w as u64 * l.h as u64) {
+            formats.push(Format {
+                w: l.w,
+                h: l.h,
+                t: if l.guessed { Some("guessed".to_string()) } else { None },
+                transform: None,
+            });
+        }
+    }
+    let profile = Profile {
+        name: name.clone(),
+        extends: None,
+        color_model: color_mode.to_string(),
+        alias: None,
+        transform: None,
+        level: None,
+        strategy: None,
+        slots: Some(slots),
+        formats,
+        remove_formats: None,
+        fingerprint,
+    };
+    let formats = profile.formats.len();
+    let output = save_profile(profile, output)?;
+    say!("profile {} ({} slots, {} formats) written to {}.",
+         data1(&name), data3(learned.len()), data3(formats), emphasize1(output.display()));
+    emit(json!({"event": "written", "path": output.display().to_string(), "profile": name,
+                "slots": learned.len(), "formats": formats}));
+    Ok(())
+}
+
+/// Writes a new profile alone in a configuration file, a fragment of the user's configuration by default.
+pub fn save_profile(profile: Profile, output: Option<PathBuf>) -> Result<PathBuf> {
+    let output = match output {
+        Some(output) => output,
+        None => {
+            let dir = Config::user_fragments_dir()?;
+            fs::create_dir_all(&dir)?;
+            dir.join(format!("{}.yaml", profile.name))
+        }
+    };
+    if output.exists() {
+        return Err(IOError::new(ErrorKind::AlreadyExists,
+                                format!("{} already exists, it is left as is", output.display())));
+    }
+    let config = Config { version: "1.1".to_string(), profiles: vec![profile] };
+    let text = serde_yaml::to_string(&config).map_err(|e| IOError::new(ErrorKind::InvalidData, e))?;
+    write_file(&output, &text)?;
+    Ok(output)
+}
+
+fn write_file(path: &Path, text: &str) -> Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(text.as_bytes())?;
+    file.write_all(b"\n")
+}
+
+#[test]
+fn test_dimensions() {
+    let known = vec![Format { w: 1280, h: 720, t: None, transform: None }, Format { w: 720, h: 1280, t: None, transform: None }];
+    assert_eq!(dimensions(720 * 1280, 720, &known), (720, 1280, false));
+    assert_eq!(dimensions(1280 * 720, 1080, &known), (1280, 720, false));
+    assert_eq!(dimensions(720 * 100, 720, &[]), (720, 100, false));
+    // a small slot is not a strip of the device's width.
+    assert_eq!(dimensions(36 * 40, 720, &[]), (40, 36, true));
+    assert_eq!(dimensions(7, 720, &[]), (7, 1, true));
+}
@@ -1,63 +1,107 @@
 extern crate ansi_term;
-use self::ansi_term::{ANSIGenericString, Colour};
+use self::ansi_term::{ANSIGenericString, Colour, Style};
+use self::output::is_colored;
+pub use self::animate::run_animate;
+pub use self::config::{run_config_check, run_config_init, run_config_list, run_config_path, run_config_show};
 pub use self::explore::run_explore;
 pub use self::guess::run_guess;
+pub use self::import::{run_import, IMPORT_TOOLS};
+pub use self::jobs::{run_jobs, Log};
+pub use self::learn::{run_learn, run_learn_from_dir};
+pub use self::output::{detail, document, emit, error, is_json, result, say, set_colored, set_document, set_json,
+                       set_verbosity, Verbosity};
+pub use self::preview::run_preview;
 pub use self::repack::run_repack;
-pub use self::unpack::run_unpack;
+pub use self::select::select_profile;
+pub use self::show::run_show;
+pub use self::slot::{decode_slot, Decoded, Slots};
+pub use self::unpack::{run_unpack, UnpackOptions};
 use std::fmt::Display;
 use super::mtklogo;
 
+/// prints a line of prose, as `println!` would: see `output::say`.
+macro_rules! say {
+    ($($arg:tt)*) => { $crate::command::say(format!($($arg)*)) };
+}
+
+/// prints a result of a command, even with `-q`: see `output::result`.
+macro_rules! result {
+    ($($arg:tt)*) => { $crate::command::result(format!($($arg)*)) };
+}
+
+/// prints a line of prose for `-v`: see `output::detail`.
+macro_rules! detail {
+    ($($arg:tt)*) => { $crate::command::detail(format!($($arg)*)) };
+}
+
+mod output;
 mod unpack;
 mod repack;
 mod explore;
 mod guess;
+mod jobs;
+mod animate;
+mod preview;
+mod show;
+mod config;
+mod learn;
+mod import;
+mod select;
+mod slot;
+
+/// colours are left out with `--color never`, see `output::set_colored`.
+fn paint<'a, S, I>(style: S, input: I) -> ANSIGenericString<'a, str>
+    where S: Into<Style>, I: Display + Sized{
+    let style = if is_colored() { style.into() } else { Style::default() };
+    style.paint(format!("{}", input))
+}
 
 /// formats a command.
 pub fn cmd<'a, I>(input: I) -> ANSIGenericString<'a, str>
     where I: Display + Sized{
-    Colour::RGB(255,153,51).bold().paint(format!("{}", input))
+    paint(Colour::RGB(255,153,51).bold(), input)
 }
 
 /// formats a warning message.
 pub fn warn<'a, I>(input: I) -> ANSIGenericString<'a, str>
     where I: Display + Sized{
-    Colour::RGB(255,204,0).bold().paint(format!("{}", input))
+    paint(Colour::RGB(255,204,0).bold(), input)
 }
 
 /// formats an error message.
 pub fn err<'a, I>(input: I) -> ANSIGenericString<'a, str>
     where I: Display + Sized{
-    Colour::RGB(204,0,0).paint(format!("{}", input))
+    paint(Colour::RGB(204,0,0), input)
 }
 
 /// emphasizing on a text information.
 pub fn emphasize1<'a, I>(input: I) -> ANSIGenericString<'a, str>
     where I: Display + Sized{
-    Colour::RGB(204,204,0).paint(format!("{}", input))
+    paint(Colour::RGB(204,204,0), input)
 }
 
 /// emphasizing on a text information (variant)
 pub fn emphasize2<'a, I>(input: I) -> ANSIGenericString<'a, str>
     where I: Display + Sized{
-    Colour::RGB(102,153,0).paint(format!("{}", input))
+    paint(Colour::RGB(102,153,0), input)
 }
 
 /// emphasizing on a data.
 pub fn data1<'a, I>(input: I) -> ANSIGenericString<'a, str>
     where I: Display + Sized{
-    Colour::RGB(153,153,255).paint(format!("{}", input))
+    paint(Colour::RGB(153,153,255), input)
 }
 
 /// emphasizing on a data (variant).
 pub fn data2<'a, I>(input: I) -> ANSIGenericString<'a, str>
     where I: Display + Sized{
-    Colour::RGB(204,51,255).paint(format!("{}", input))
+    paint(Colour::RGB(204,51,255), input)
 }
 
 /// emphasizing on a data (variant).
 pub fn data3<'a, I>(input: I) -> ANSIGenericString<'a, str>
     where I: Display + Sized{
-    Colour::RGB(51,204,255).paint(format!("{}", input))
+    paint(Colour::RGB(51,204,255), input)
 }
 
 
@@ -0,0 +1,97 @@
+use serde_json::Value;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// How much prose is printed, from `-q` to `-v`.
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+// set once from the global options, before any command runs.
+static JSON: AtomicBool = AtomicBool::new(false);
+static DOCUMENT: AtomicBool = AtomicBool::new(false);
+static COLORED: AtomicBool = AtomicBool::new(true);
+static VERBOSITY: AtomicUsize = AtomicUsize::new(Verbosity::Normal as usize);
+
+/// Results become JSON lines on stdout, prose goes to stderr.
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// The command prints a document on stdout, such as a YAML profile: prose goes to stderr.
+pub fn set_document(document: bool) {
+    DOCUMENT.store(document, Ordering::Relaxed);
+}
+
+pub fn set_colored(colored: bool) {
+    COLORED.store(colored, Ordering::Relaxed);
+}
+
+pub fn is_colored() -> bool {
+    COLORED.load(Ordering::Relaxed)
+}
+
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as usize, Ordering::Relaxed);
+}
+
+fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+fn print<D: Display>(line: D) {
+    if is_json() || DOCUMENT.load(Ordering::Relaxed) {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+/// Prints a line of prose, unless `-q`: on stdout, or on stderr when results are JSON lines.
+pub fn say<D: Display>(line: D) {
+    if verbosity() >= Verbosity::Normal {
+        print(line);
+    }
+}
+
+/// Prints a result of a command as text, even with `-q`: where prose goes.
+pub fn result<D: Display>(line: D) {
+    print(line);
+}
+
+/// Prints a document on stdout, even with `-q`, unless results are JSON lines: they carry it.
+pub fn document<D: Display>(text: D) {
+    if !is_json() {
+        println!("{}", text);
+    }
+}
+
+/// Prints a line of prose only asked for with `-v`.
+pub fn detail<D: Display>(line: D) {
+    if verbosity() >= Verbosity::Verbose {
+        print(line);
+    }
+}
+
+/// Prints an error on stderr, even with `-q`.
+pub fn error<D: Display>(line: D) {
+    eprintln!("{}", line);
+}
+
+/// Prints a result as a JSON line, only when they are asked for: prose tells it otherwise.
+pub fn emit(record: Value) {
+    if is_json() {
+        println!("{}", record);
+    }
+}
@@ -0,0 +1,165 @@
+use std::cmp;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Result};
+use std::path::PathBuf;
+use super::{cmd, data1, data2, data3, decode_slot, emit, emphasize1, err, warn};
+use super::mtklogo::{ColorMode, LogoImage};
+use super::mtklogo::utils::image;
+use super::super::config::Profile;
+
+/// Space around a thumbnail, in pixels.
+const PADDING: u32 = 8;
+/// Labels are drawn with the tiny font, enlarged that many times.
+const LABEL_SCALE: u32 = 2;
+const BACKGROUND: [u8; 4] = [0x20, 0x20, 0x20, 0xFF];
+const PLACEHOLDER: [u8; 4] = [0x55, 0x55, 0x55, 0xFF];
+const INK: [u8; 4] = [0xEE, 0xEE, 0xEE, 0xFF];
+const WARNING_INK: [u8; 4] = [0xFF, 0xCC, 0x00, 0xFF];
+
+/// A tiny 3x5 pixels font, digits only, enough to label slots.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+pub fn run_preview(profile: Profile, flip: bool, columns: u32, cell: u32,
+                   path: PathBuf, output: PathBuf) -> Result<()> {
+    let mtk_color_model = ColorMode::by_name(&profile.color_model)?;
+    say!("{} file {} with profile {}, color mode {}, to contact sheet {}.",
+         cmd("preview"),
+         emphasize1(path.display()),
+         data1(&profile.name),
+         data2(mtk_color_model),
+         emphasize1(output.display()));
+
+    // reads whole image in memory.
+    let mut reader = BufReader::new(File::open(path)?);
+    let image = LogoImage::read(&mut reader)?;
+
+    let count = image.blobs.len() as u32;
+    let columns = cmp::max(1, cmp::min(columns, count));
+    let rows = count.div_ceil(columns);
+    let cell = cmp::max(1, cell);
+    let label_h = 5 * LABEL_SCALE + PADDING;
+    let too_large = || Error::new(ErrorKind::InvalidInput,
+                                  format!("cells of {} pixels make a contact sheet too large", cell));
+    let cell_w = cell.checked_add(2 * PADDING).ok_or_else(too_large)?;
+    let cell_h = cell.checked_add(label_h + 2 * PADDING).ok_or_else(too_large)?;
+    let mut sheet = match (cell_w.checked_mul(columns), cell_h.checked_mul(rows)) {
+        (Some(w), Some(h)) => Sheet::new(w, h)?,
+        _ => return Err(too_large()),
+    };
+
+    for (id, blob) in image.blobs.iter().enumerate() {
+        let id32 = id as u32;
+        // top left corner of the thumbnail area.
+        let x = (id32 % columns) * cell_w + PADDING;
+        let y = (id32 / columns) * cell_h + PADDING;
+        sheet.text(x, y, &format!("{}", id), INK);
+        let y = y + label_h;
+        let decoded = profile.slot_color_mode(id)
+            .and_then(|color_mode| decode_slot(blob, color_mode, |sz| profile.slot_format(id, sz, flip)));
+        match decoded {
+            Ok(decoded) => {
+                // fits the image in the cell, keeping its aspect ratio: the smaller side is at most `cell`.
+                let scale = |side: u32, other: u32| {
+                    cmp::max(1, (side as u64 * cell as u64).checked_div(other as u64).unwrap_or(0) as u32)
+                };
+                let (tw, th) = if decoded.w >= decoded.h {
+                    (cell, scale(decoded.h, decoded.w))
+                } else {
+                    (scale(decoded.w, decoded.h), cell)
+                };
+                let thumbnail = image::scale_nearest(&decoded.rgba, decoded.w, decoded.h, tw, th);
+                sheet.blit(&thumbnail, tw, th, x + (cell - tw) / 2, y + (cell - th) / 2);
+                say!("slot {} is a {}x{} image.", data1(id), data3(decoded.w), data3(decoded.h));
+                emit(json!({"event": "slot", "slot": id, "width": decoded.w, "height": decoded.h}));
+            }
+            Err(e) => {
+                // placeholder, telling the size of the blob.
+                sheet.fill(x, y, cell, cell, PLACEHOLDER);
+                sheet.text(x + PADDING, y + PADDING, &format!("{}", blob.len()), WARNING_INK);
+                say!("{} {} ({} bytes): {}",
+                     warn("Cannot preview slot"), data1(id), data2(blob.len()), err(&e));
+                emit(json!({"event": "slot_error", "slot": id, "bytes": blob.len(), "error": e.to_string()}));
+            }
+        }
+    }
+
+    let writer = BufWriter::new(File::create(&output)?);
+    image::rgba_to_png(writer, &sheet.rgba, sheet.w, sheet.h)?;
+    say!("successfully previewed {} slots to {}", data1(count), emphasize1(output.display()));
+    emit(json!({"event": "written", "path": output.display().to_string(), "slots": count}));
+    Ok(())
+}
+
+/// A RGBA raster we draw on.
+struct Sheet {
+    rgba: Vec<u8>,
+    w: u32,
+    h: u32,
+}
+
+impl Sheet {
+    /// Fails when the raster wouldn't fit in memory.
+    fn new(w: u32, h: u32) -> Result<Sheet> {
+        let len = (w as usize).checked_mul(h as usize).and_then(|pixels| pixels.checked_mul(4))
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput,
+                                      format!("a contact sheet of {}x{} pixels is too large", w, h)))?;
+        let rgba = BACKGROUND.iter().cloned().cycle().take(len).collect();
+        Ok(Sheet { rgba, w, h })
+    }
+
+    #[inline(always)]
+    fn put(&mut self, x: u32, y: u32, color: &[u8]) {
+        if x < self.w && y < self.h {
+            let offset = (y as usize * self.w as usize + x as usize) * 4;
+            // blends over the background, which is opaque.
+            let alpha = color[3] as u32;
+            for (under, over) in self.rgba[offset..offset + 3].iter_mut().zip(color.iter()) {
+                *under = ((*over as u32 * alpha + *under as u32 * (255 - alpha)) / 255) as u8;
+            }
+        }
+    }
+
+    fn fill(&mut self, x: u32, y: u32, w: u32, h: u32, color: [u8; 4]) {
+        for dy in 0..h {
+            for dx in 0..w {
+                self.put(x + dx, y + dy, &color);
+            }
+        }
+    }
+
+    fn blit(&mut self, rgba: &[u8], w: u32, h: u32, x: u32, y: u32) {
+        for (i, pixel) in rgba.chunks(4).enumerate() {
+            let i = i as u32;
+            self.put(x + i % w, y + i / w, pixel);
+        }
+        debug_assert_eq!(rgba.len(), w as usize * h as usize * 4);
+    }
+
+    /// Writes digits, other characters are left blank.
+    fn text(&mut self, x: u32, y: u32, text: &str, color: [u8; 4]) {
+        for (i, c) in text.chars().enumerate() {
+            let left = x + i as u32 * 4 * LABEL_SCALE;
+            if let Some(glyph) = c.to_digit(10).map(|d| DIGITS[d as usize]) {
+                for (row, bits) in glyph.iter().enumerate() {
+                    for col in 0..3 {
+                        if bits & (0b100 >> col) != 0 {
+                            self.fill(left + col * LABEL_SCALE, y + row as u32 * LABEL_SCALE,
+                                      LABEL_SCALE, LABEL_SCALE, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,30 +1,61 @@
 use std::fs::File;
-use std::io::{BufWriter, Error as IOError, ErrorKind, Result};
+use std::io::{BufReader, BufWriter, Error as IOError, ErrorKind, Result};
 use std::path::PathBuf;
-use super::{cmd, data1, data2, emphasize1, emphasize2};
-use super::mtklogo::{ContentType, FileInfo, LogoImage};
-use super::mtklogo::utils::{image, image::ImageIO, load_raw, z_lib};
+use super::{cmd, data1, data2, data3, emit, emphasize1, emphasize2, run_jobs, warn, Log};
+use super::mtklogo::{ColorMode, ContentType, FileInfo, LogoImage};
+use super::mtklogo::utils::{codec::Codec, encoding::Encoding, find_level, formats::ImageFormat, image,
+                            image::{ImageIO, Transform}, load_raw, stream};
+use super::super::config::{Profile, Slot};
 
-pub fn run_repack(outpath: PathBuf, files: Vec<PathBuf>, strip_alpha: bool) -> Result<()> {
-    println!("{} {} files into {} stripping alpha: {}.",
-             cmd("repack"),
-             data1(files.len()),
-             emphasize1(outpath.display()),
-             data2(strip_alpha));
+pub fn run_repack(outpath: PathBuf, files: Vec<PathBuf>, strip_alpha: bool,
+                  codec: Box<dyn Codec>, reference: Option<PathBuf>, profile: Option<Profile>,
+                  jobs: usize) -> Result<()> {
+    say!("{} {} files into {} stripping alpha: {}, compression {} level {}.",
+         cmd("repack"),
+         data1(files.len()),
+         emphasize1(outpath.display()),
+         data2(strip_alpha),
+         data2(codec.name()),
+         data2(codec.level()));
 
+    // The original image, whose compression settings we try to reproduce.
+    let reference = match reference {
+        Some(path) => {
+            say!("matching compression of {}.", emphasize1(path.display()));
+            let mut reader = BufReader::new(File::open(path)?);
+            Some(LogoImage::read(&mut reader)?)
+        }
+        None => None
+    };
+    if let Some(ref profile) = profile {
+        say!("checking slots declared in profile {}.", data1(&profile.name));
+    }
     // Reads input file meta information.
     let packable_files = reorder(files)?;
-    // extracts blob data.
+    // extracts blob data, in parallel: blobs still come in the files order.
+    let done = run_jobs(&packable_files, jobs, |file, log| -> Result<Vec<u8>> {
+        let original = reference.as_ref().and_then(|image| image.blobs.get(file.info.id));
+        let slot = match profile {
+            Some(ref profile) => profile.slot(file.info.id)?,
+            None => None,
+        };
+        let blob = import_logo(file, strip_alpha, codec.as_ref(), original, slot.as_ref(), log)?;
+        log.emit(json!({"event": "slot", "slot": file.info.id, "path": file.path.display().to_string(),
+                        "bytes": blob.len()}));
+        Ok(blob)
+    })?;
     let mut blobs = Vec::with_capacity(packable_files.len());
-    for file in packable_files.iter() {
-        blobs.push(import_logo(file, strip_alpha)?);
+    for (log, blob) in done {
+        log.print();
+        blobs.push(blob?);
     }
     let count = blobs.len();
     let image = LogoImage::new_blobs(blobs);
     // saves it
     let mut writer = BufWriter::new(File::create(&outpath)?);
     image.write(&mut writer)?;
-    println!("successfully repacked {} logos to {}", data1(count), emphasize1(outpath.display()));
+    say!("successfully repacked {} logos to {}", data1(count), emphasize1(outpath.display()));
+    emit(json!({"event": "written", "path": outpath.display().to_string(), "slots": count}));
     Ok(())
 }
 
@@ -33,23 +64,92 @@ struct PackableFile {
     info: FileInfo,
 }
 
-fn import_logo(logo: &PackableFile, strip_alpha: bool) -> Result<Vec<u8>> {
+fn import_logo(logo: &PackableFile, strip_alpha: bool, codec: &dyn Codec,
+               original: Option<&Vec<u8>>, slot: Option<&Slot>, log: &mut Log) -> Result<Vec<u8>> {
     let file = File::open(&logo.path)?;
-    match logo.info.content_type {
+    let device = match logo.info.content_type {
         ContentType::Z => {
-            load_raw(file)
+            return load_raw(file);
+        }
+        ContentType::Bin(ref color_mode) => {
+            // already in device format.
+            let device = load_raw(file)?;
+            check_slot(logo, slot, color_mode, device.len() / color_mode.bytes_per_pixel() as usize, None)?;
+            device
         }
-        ContentType::PNG(ref color_mode) => {
-            // loads png as rgba
-            let (mut rgba, w, h) = image::png_to_rgba(file)?;
+        ContentType::Rgba(ref color_mode) => {
+            // pixels in device order, with no dimensions.
+            let mut rgba = load_raw(file)?;
+            check_slot(logo, slot, color_mode, rgba.len() / 4, None)?;
+            if strip_alpha { image::strip_alpha(&mut rgba) };
+            color_mode.rgba_to_device(&rgba, (rgba.len() / 4) as u32, 1)?
+        }
+        ContentType::Image(ref color_mode, format) => {
+            // straight PNG logos with nothing to match are streamed row by row.
+            if format == ImageFormat::Png && logo.info.transform == Transform::Identity && original.is_none() {
+                match stream::png_to_blob(BufReader::new(file), color_mode, strip_alpha, logo.info.encoding, codec) {
+                    Ok((blob, w, h)) => {
+                        check_slot(logo, slot, color_mode, (w * h) as usize, Some((w, h)))?;
+                        return Ok(blob);
+                    }
+                    Err(ref e) if e.kind() == ErrorKind::Unsupported => (),
+                    Err(e) => return Err(e),
+                }
+            }
+            // loads image as rgba
+            let (mut rgba, w, h) = format.read_rgba(BufReader::new(File::open(&logo.path)?))?;
             // do we want to strip alpha?
             if strip_alpha { image::strip_alpha(&mut rgba) };
+            // reverts unpack's transform, back to device raster.
+            let (rgba, w, h) = logo.info.transform.inverse().apply(&rgba, w, h)?;
+            check_slot(logo, slot, color_mode, (w * h) as usize, Some((w, h)))?;
             // converts to device format.
-            let device = color_mode.rgba_to_device(&rgba as &[u8], w, h)?;
-            // zipped data.
-            z_lib::deflate(&device)
+            color_mode.rgba_to_device(&rgba as &[u8], w, h)?
+        }
+    };
+    // can we reproduce the original bytes? only zlib has levels to try.
+    if let (Some(original), Encoding::Zlib) = (original, logo.info.encoding) {
+        match find_level(&device, original, codec) {
+            Some((level, strategy)) => {
+                log.say(format!("slot {} reproduced with compression level {}, strategy {}.",
+                                data1(logo.info.id), data3(level), data3(strategy)));
+                log.emit(json!({"event": "reproduced", "slot": logo.info.id, "reproduced": true,
+                                "level": level, "strategy": strategy.to_string()}));
+                return Ok(original.clone());
+            }
+            None => {
+                log.say(format!("{} slot {}, it was modified or compressed otherwise.",
+                                warn("Could not reproduce"), data1(logo.info.id)));
+                log.emit(json!({"event": "reproduced", "slot": logo.info.id, "reproduced": false}));
+            }
         }
     }
+    // zipped data, or whatever the slot was.
+    logo.info.encoding.encode(&device, codec)
+}
+
+/// Checks a logo against the profile's entry for its slot, if any.
+/// The color mode is only checked when the slot declares its own.
+fn check_slot(logo: &PackableFile, slot: Option<&Slot>, color_mode: &ColorMode,
+              pixels: usize, dimensions: Option<(u32, u32)>) -> Result<()> {
+    let slot = match slot {
+        Some(slot) => slot,
+        None => return Ok(()),
+    };
+    let mismatch = |what: String| Err(IOError::new(
+        ErrorKind::InvalidData,
+        format!("{} is {}, but slot {} is declared {}x{}{} in the profile", logo.path.display(), what,
+                logo.info.id, slot.w, slot.h, slot.color_model.as_ref().map_or(String::new(), |m| format!(" {}", m)))));
+    if let Some(ref name) = slot.color_model {
+        if ColorMode::by_name(name)? != color_mode {
+            return mismatch(format!("{}", color_mode));
+        }
+    }
+    match dimensions {
+        Some((w, h)) if (w, h) != (slot.w, slot.h) => mismatch(format!("{}x{}", w, h)),
+        None if pixels as u64 != slot.pixels() => mismatch(format!("{} pixels", pixels)),
+        _ => Ok(()),
+    }
 }
 
 fn reorder(files: Vec<PathBuf>) -> Result<Vec<PackableFile>> {
@@ -62,11 +162,18 @@ fn reorder(files: Vec<PathBuf>) -> Result<Vec<PackableFile>> {
         let info = FileInfo::from_name(path)?;
         match &info.content_type {
             ContentType::Z =>
-                println!("file {} is slot {} in raw z format.",
-                         emphasize1(path), data1(info.id)),
-            ContentType::PNG(p) =>
-                println!("file {} is slot {} in {} format.",
-                    emphasize1(path), data1(info.id), emphasize2(p)),
+                say!("file {} is slot {} in raw z format.",
+                     emphasize1(path), data1(info.id)),
+            ContentType::Image(p, format) =>
+                say!("file {} is slot {} in {} format ({}), transform {}, encoding {}.",
+                    emphasize1(path), data1(info.id), emphasize2(p), data2(format), data2(info.transform),
+                    data2(info.encoding)),
+            ContentType::Bin(p) =>
+                say!("file {} is slot {} in {} format (device dump), encoding {}.",
+                    emphasize1(path), data1(info.id), emphasize2(p), data2(info.encoding)),
+            ContentType::Rgba(p) =>
+                say!("file {} is slot {} in {} format (rgba pixels), encoding {}.",
+                    emphasize1(path), data1(info.id), emphasize2(p), data2(info.encoding)),
         }
         analyzed.push(PackableFile { path: file.clone(), info });
     }
@@ -0,0 +1,51 @@
+use std::fs::File;
+use std::io::{BufReader, Error as IOError, ErrorKind, Result};
+use std::path::Path;
+use super::{data1, data3, emit, emphasize1, warn};
+use super::mtklogo::LogoImage;
+use super::super::config::{Config, Profile, Traits};
+
+/// Confidence from which the best profile is taken,
+const CONFIDENT: f64 = 0.8;
+/// as long as it's ahead of the next one by this margin.
+const MARGIN: f64 = 0.1;
+/// How many profiles are listed when none is taken.
+const CANDIDATES: usize = 3;
+
+/// Picks the profile of a logo image when the user does not tell it: the best match when it's clear,
+/// otherwise the default profile, after listing the closest ones. The measured traits come along.
+pub fn select_profile(config: &Config, path: &Path) -> Result<(Profile, Traits)> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let image = LogoImage::read(&mut reader)?;
+    let traits = Traits::measure(&image)?;
+    let ranked = config.rank(&traits);
+    let next = ranked.get(1).map_or(0.0, |(_, confidence)| *confidence);
+    if let Some((profile, confidence)) = ranked.first() {
+        if *confidence >= CONFIDENT && confidence - next >= MARGIN {
+            say!("file {} matches profile {} ({} confidence).",
+                 emphasize1(path.display()), data1(&profile.name), data3(percent(*confidence)));
+            emit(json!({"event": "profile", "profile": profile.name, "confidence": confidence}));
+            return Ok((profile.clone(), traits));
+        }
+    }
+    say!("{} file {}, the closest profiles are:", warn("No profile clearly matches"), emphasize1(path.display()));
+    for (profile, confidence) in ranked.iter().take(CANDIDATES) {
+        say!("  {} {}", data1(format!("{:<24}", profile.name)), data3(percent(*confidence)));
+        emit(json!({"event": "candidate", "profile": profile.name, "confidence": confidence}));
+    }
+    for (profile, confidence) in ranked.iter().skip(CANDIDATES) {
+        detail!("  {} {}", data1(format!("{:<24}", profile.name)), data3(percent(*confidence)));
+    }
+    match config.profile("default") {
+        Ok(profile) => {
+            say!("using profile {}, pass {} to choose another one.", data1(&profile.name), emphasize1("--profile"));
+            emit(json!({"event": "profile", "profile": profile.name}));
+            Ok((profile, traits))
+        }
+        Err(_) => Err(IOError::new(ErrorKind::InvalidInput, "no profile matches the logo image, please pass --profile")),
+    }
+}
+
+fn percent(confidence: f64) -> String {
+    format!("{:.0}%", confidence * 100.0)
+}
@@ -0,0 +1,100 @@
+extern crate ansi_term;
+extern crate terminal_size;
+
+use self::ansi_term::Colour;
+use self::terminal_size::{terminal_size, Width};
+use std::cmp;
+use std::env;
+use std::fs::File;
+use std::io::{BufReader, Error as IOError, ErrorKind, Result};
+use std::path::PathBuf;
+use super::{cmd, data1, data2, data3, decode_slot, emit, emphasize1};
+use super::output::is_colored;
+use super::mtklogo::LogoImage;
+use super::mtklogo::utils::image;
+use super::super::config::{Format, Profile};
+
+/// Used when the terminal does not tell its width.
+const DEFAULT_COLUMNS: u32 = 80;
+/// Without colours, from dark to bright over a black terminal.
+const SHADES: &[u8] = b" .:-=+*#%@";
+
+pub fn run_show(profile: Profile, slot: usize, width: Option<u32>, columns: Option<u32>, flip: bool,
+                path: PathBuf) -> Result<()> {
+    let mtk_color_model = profile.slot_color_mode(slot)?;
+    say!("{} slot {} of file {} with profile {}, color mode {}.",
+         cmd("show"),
+         data1(slot),
+         emphasize1(path.display()),
+         data1(&profile.name),
+         data2(mtk_color_model));
+
+    // a known width takes precedence on the profile's formats, just like 'explore'.
+    let format_provider = |sz: u32| match width {
+        Some(w) => {
+            // a line wider than the blob never fits it.
+            let line = w.checked_mul(mtk_color_model.bytes_per_pixel()).unwrap_or(0);
+            let h = sz.checked_div(line).unwrap_or(0);
+            if h > 0 && h * line == sz {
+                Ok(Format { w, h, t: None, transform: profile.transform.clone() })
+            } else {
+                Err(IOError::new(ErrorKind::InvalidData,
+                                 format!("{} bytes cannot be {} pixels wide in {}", sz, w, mtk_color_model)))
+            }
+        }
+        None => profile.slot_format(slot, sz, flip),
+    };
+
+    // reads whole image in memory.
+    let mut reader = BufReader::new(File::open(path)?);
+    let image = LogoImage::read(&mut reader)?;
+    let blob = image.blobs.get(slot).ok_or_else(|| IOError::new(
+        ErrorKind::InvalidInput, format!("logo image has no slot {}", slot)))?;
+    let decoded = decode_slot(blob, mtk_color_model, format_provider)?;
+    result!("slot {} is a {}x{} image.", data1(slot), data3(decoded.w), data3(decoded.h));
+    emit(json!({"event": "slot", "slot": slot, "width": decoded.w, "height": decoded.h}));
+    if decoded.w == 0 || decoded.h == 0 {
+        return Err(IOError::new(ErrorKind::InvalidData, format!("slot {} is an empty image", slot)));
+    }
+
+    // fits the terminal width, a character cell holds two pixels stacked vertically.
+    let columns = columns.unwrap_or_else(terminal_columns);
+    let tw = cmp::max(1, cmp::min(columns, decoded.w));
+    let th = cmp::max(2, ((decoded.h as u64 * tw as u64 / decoded.w as u64 + 1) & !1) as u32);
+    let scaled = image::scale_nearest(&decoded.rgba, decoded.w, decoded.h, tw, th);
+    let pixel = |x: u32, y: u32| -> (u8, u8, u8) {
+        let offset = (y as usize * tw as usize + x as usize) * 4;
+        // blends over a black terminal.
+        let alpha = scaled[offset + 3] as u32;
+        let channel = |c: usize| (scaled[offset + c] as u32 * alpha / 255) as u8;
+        (channel(0), channel(1), channel(2))
+    };
+    let colored = is_colored();
+    let cell = |x: u32, y: u32| -> String {
+        let ((r, g, b), (r2, g2, b2)) = (pixel(x, y), pixel(x, y + 1));
+        if colored {
+            // upper half block: foreground is the top pixel, background the bottom one.
+            Colour::RGB(r, g, b).on(Colour::RGB(r2, g2, b2)).paint("\u{2580}").to_string()
+        } else {
+            // the luma of both pixels picks a shade.
+            let luma = (r as u32 + r2 as u32) * 299 + (g as u32 + g2 as u32) * 587 + (b as u32 + b2 as u32) * 114;
+            let shade = luma as usize * (SHADES.len() - 1) / (2 * 255 * 1000);
+            (SHADES[shade] as char).to_string()
+        }
+    };
+    for y in (0..th).step_by(2) {
+        let line: Vec<String> = (0..tw).map(|x| cell(x, y)).collect();
+        result!("{}", line.concat());
+    }
+    Ok(())
+}
+
+/// Terminal width, as the terminal tells it, or the shell when the output is not a terminal.
+fn terminal_columns() -> u32 {
+    match terminal_size() {
+        Some((Width(columns), _)) if columns > 0 => columns as u32,
+        _ => env::var("COLUMNS").ok()
+            .and_then(|columns| columns.parse::<u32>().ok())
+            .unwrap_or(DEFAULT_COLUMNS),
+    }
+}
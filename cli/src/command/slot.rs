@@ -0,0 +1,68 @@
+use std::cmp;
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
+use std::ops::RangeInclusive;
+use super::mtklogo::ColorMode;
+use super::mtklogo::utils::{codec, encoding, encoding::Encoding, image::ImageIO, image::Transform};
+use super::super::config::Format;
+
+/// A slot decoded as an upright RGBA image.
+pub struct Decoded {
+    pub rgba: Vec<u8>,
+    pub w: u32,
+    pub h: u32,
+    /// Transform which was applied to the device raster.
+    pub transform: Transform,
+    /// How the slot was stored.
+    pub encoding: Encoding,
+}
+
+/// Decodes a slot, finds its dimension and converts it to an upright RGBA image.
+pub fn decode_slot<F>(blob: &[u8], color_mode: &ColorMode, format_provider: F) -> Result<Decoded>
+    where F: Fn(u32) -> Result<Format> {
+    let (encoding, inflated) = encoding::decode(blob, codec::inflater()?.as_ref());
+    let format = format_provider(inflated.len() as u32)?;
+    let transform = format.transform()?;
+    let rgba = color_mode.device_to_rgba(&inflated, format.w, format.h)?;
+    let (rgba, w, h) = transform.apply(&rgba, format.w, format.h)?;
+    Ok(Decoded { rgba, w, h, transform, encoding })
+}
+
+/// Slots picked with `--slots`, as inclusive ranges which are only expanded against the image's slots.
+#[derive(Debug, Default)]
+pub struct Slots(pub Vec<RangeInclusive<usize>>);
+
+impl Slots {
+    pub fn contains(&self, id: usize) -> bool {
+        self.0.iter().any(|range| range.contains(&id))
+    }
+
+    /// Every picked slot in order, failing on the first one the image does not have.
+    pub fn list(&self, count: usize) -> Result<Vec<usize>> {
+        match self.0.iter().find(|range| *range.end() >= count) {
+            Some(range) => Err(Error::new(ErrorKind::InvalidInput,
+                                          format!("logo image has no slot {}", cmp::max(*range.start(), count)))),
+            None => Ok(self.0.iter().cloned().flatten().collect()),
+        }
+    }
+}
+
+impl fmt::Display for Slots {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ranges: Vec<String> = self.0.iter().map(|range| match (range.start(), range.end()) {
+            (first, last) if first == last => first.to_string(),
+            (first, last) => format!("{}-{}", first, last),
+        }).collect();
+        write!(f, "{}", ranges.join(","))
+    }
+}
+
+#[test]
+fn test_slots_are_not_expanded_beyond_the_image() {
+    let slots = Slots(vec![3..=4, 0..=0, 2..=2_000_000_000]);
+    assert!(slots.contains(1_999_999_999));
+    assert!(!slots.contains(1));
+    assert_eq!(slots.to_string(), "3-4,0,2-2000000000");
+    assert_eq!(Slots(vec![3..=4, 0..=0]).list(5).unwrap(), vec![3, 4, 0]);
+    assert_eq!(slots.list(5).err().unwrap().to_string(), "logo image has no slot 5");
+}
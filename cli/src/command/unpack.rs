@@ -1,152 +1,267 @@
-use Profile;
-use std::error::Error;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Error as IOError, ErrorKind, Result, Write};
-use std::path::PathBuf;
-use super::{cmd, data1, data2, data3, emphasize1, emphasize2, err, warn};
+use std::path::{Path, PathBuf};
+use super::{cmd, data1, data2, data3, decode_slot, emit, emphasize1, emphasize2, err, run_jobs, warn, Decoded, Log, Slots};
 use super::mtklogo::{ColorMode, ContentType, FileInfo, LogoImage};
-use super::mtklogo::utils::{image::ImageIO, z_lib};
-use super::super::config::{Config, Format};
+use super::mtklogo::utils::{codec, encoding, encoding::Encoding, formats::ImageFormat, image::{ImageIO, Transform}, stream};
+use super::super::config::{Format, Profile, Traits};
 
-pub fn run_unpack(config: Config, slots: Option<Vec<usize>>, profile_name: &str,
-                  mode: Option<&str>, flip: bool, zip: bool, check: bool,
-                  path: PathBuf, output: PathBuf) -> Result<()> {
-    /// does profile have this name or this alias?
-    fn match_name_or_alias(profile: &Profile, name: &str) -> bool {
-        if profile.name.eq(name) {
-            true
-        } else {
-            match profile.alias {
-                None => false,
-                Some(ref aliases) => aliases.contains(&name.to_string())
-            }
-        }
-    }
-    // What is active profile?
-    let maybe_profile =
-        config.profiles.iter().find(|profile|
-            match_name_or_alias(profile, profile_name)
-        );
-    let mut profile = match maybe_profile {
-        Some(p) => Ok(p.clone()),
-        None => Err(IOError::new(ErrorKind::InvalidData,
-                                 format!("profile '{}' is not declared in configuration file", profile_name)))
-    }?;
-    // User may override color model.
-    if let Some(model) = mode {
-        profile = profile.with_color_model(String::from(model));
-    };
+/// How `unpack` extracts the slots.
+pub struct UnpackOptions {
+    /// Only these slots are converted, the others are extracted raw.
+    pub slots: Option<Slots>,
+    pub flip: bool,
+    /// Every slot is extracted raw.
+    pub zip: bool,
+    /// Tells what would be extracted, without writing anything.
+    pub check: bool,
+    /// Extension the slots are exported to.
+    pub format: String,
+    pub jobs: usize,
+}
+
+/// `traits` are the slots measured to select the profile, when it was not given: they are not measured again.
+pub fn run_unpack(profile: Profile, traits: Option<Traits>, path: PathBuf, output: PathBuf,
+                  options: UnpackOptions) -> Result<()> {
+    let UnpackOptions { slots, flip, zip, check, format, jobs } = options;
     let mtk_color_model = ColorMode::by_name(&profile.color_model)?;
-    println!("{} file {} with profile {}, color mode {}, flip orientation: {} to directory {}.",
-             cmd("unpack"),
-             emphasize1(path.display()),
-             data1(profile_name),
-             data2(format!("{}", mtk_color_model)),
-             emphasize1(format!("{}", flip)),
-             emphasize1(output.display()));
+    let default_transform = profile.transform()?;
+    // checks the format once, slots may have their own color mode.
+    ContentType::by_extension(&format, mtk_color_model)?;
+    say!("{} file {} with profile {}, color mode {}, transform {}, flip orientation: {} to directory {} as {}.",
+         cmd("unpack"),
+         emphasize1(path.display()),
+         data1(&profile.name),
+         data2(format!("{}", mtk_color_model)),
+         data2(default_transform),
+         emphasize1(format!("{}", flip)),
+         emphasize1(output.display()),
+         data2(&format));
 
     // Opens the file
-    let f = File::open(path)?;
-
-    let format_provider = |sz: u32| profile.guess_format(sz, flip);
+    let f = File::open(&path)?;
 
     // Reads through it.
     let mut reader = BufReader::new(f);
     // reads whole image in memory.
     let image = LogoImage::read(&mut reader)?;
-    println!("logo image has {} slots", data1(image.blobs.len()));
-    // let format_provider = &conf::guess_format;
-    for (id, blob) in image.blobs.iter().enumerate() {
+    say!("logo image has {} slots", data1(image.blobs.len()));
+    emit(json!({"event": "image", "path": path.display().to_string(), "slots": image.blobs.len()}));
+    // slots are decoded in parallel, then reported in order.
+    let indexed: Vec<(usize, &Vec<u8>)> = image.blobs.iter().enumerate().collect();
+    let done = run_jobs(&indexed, jobs, |&(id, blob), log| {
         let should_extract_zip = match slots {
             None => false,
-            Some(ref s) => !s.contains(&id)
+            Some(ref s) => !s.contains(id)
         };
+        // the profile's entry for this slot prevails, then its size tells the format.
+        let color_mode = profile.slot_color_mode(id)?;
+        let name = profile.slot(id)?.and_then(|slot| slot.name);
+        let format_provider = |sz: u32| profile.slot_format(id, sz, flip);
+        let info = if zip || should_extract_zip {
+            FileInfo::from_info(id, true, color_mode)
+        } else {
+            FileInfo::from_info(id, false, color_mode).with_content_type(ContentType::by_extension(&format, color_mode)?)
+        }.with_name(name);
         if check {
-            check_logo(id, blob, zip || should_extract_zip, &mtk_color_model, &output, format_provider);
+            check_logo(&info, blob, &output, format_provider, log);
+            Ok(())
         } else {
-            extract_logo(id, blob, zip || should_extract_zip, &mtk_color_model, &output, format_provider)?;
+            let measured = traits.as_ref().and_then(|traits| traits.slot(id));
+            extract_logo(&info, blob, measured, color_mode, &output, format_provider, log)
         }
+    })?;
+    for (log, result) in done {
+        log.print();
+        result?;
     }
     Ok(())
 }
 
-fn extract_logo<F>(id: usize, blob: &Vec<u8>, zip: bool, color_mode: &ColorMode, outpath: &PathBuf, format_provider: F)
-                   -> Result<()>
+fn extract_logo<F>(info: &FileInfo, blob: &[u8], measured: Option<(Encoding, u64)>, color_mode: &ColorMode,
+                   outpath: &Path, format_provider: F, log: &mut Log) -> Result<()>
     where F: Fn(u32) -> Result<Format> {
-    let info = FileInfo::from_info(id, zip, color_mode);
-    // computes the output name.
-    let output_file = outpath.join(info.filename());
-    match &info.content_type {
+    let exported = match info.content_type {
         ContentType::Z =>
-            export_raw(&info, &output_file, blob),
-        ContentType::PNG(e) => {
-            export_png(&info, &output_file, blob, color_mode, format_provider)
-                .or_else(|er| {
-                    println!("{} slot {} as {} because {}. Falling back to raw .z.",
-                             warn("Could not export"),
-                             data1(id), emphasize1(e),
-                             err(er.description()), );
-                    // invalidates names.
-                    let info = FileInfo::from_info(id, true, color_mode);
-                    // computes the output name.
-                    let output_file = outpath.join(info.filename());
-                    export_raw(&info, &output_file, blob)
-                })
+            return export_raw(info, &outpath.join(info.filename()), blob, log),
+        ContentType::Image(_, format) =>
+            export_image(info, outpath, blob, measured, (color_mode, format), format_provider, log),
+        ContentType::Bin(_) => export_device(info, outpath, blob, log),
+        ContentType::Rgba(_) => export_rgba(info, outpath, blob, color_mode, log),
+    };
+    match exported {
+        Ok(()) => Ok(()),
+        Err(er) => {
+            log.say(format!("{} slot {} as {} because {}. Falling back to raw .z.",
+                            warn("Could not export"),
+                            data1(info.id), emphasize1(info.filename()),
+                            err(&er)));
+            log.emit(json!({"event": "slot_error", "slot": info.id, "error": er.to_string()}));
+            // invalidates names.
+            let info = FileInfo::from_info(info.id, true, color_mode).with_name(info.name.clone());
+            // computes the output name.
+            let output_file = outpath.join(info.filename());
+            export_raw(&info, &output_file, blob, log)
         }
     }
 }
 
-fn check_logo<F>(id: usize, blob: &Vec<u8>, zip: bool, color_mode: &ColorMode, outpath: &PathBuf, format_provider: F)
+fn check_logo<F>(info: &FileInfo, blob: &[u8], outpath: &Path, format_provider: F, log: &mut Log)
     where F: Fn(u32) -> Result<Format> {
-    let info = FileInfo::from_info(id, zip, color_mode);
-    // computes the output name.
-    let output_file = outpath.join(info.filename());
     match info.content_type {
-        ContentType::Z =>
-            println!("slot {} is {} bytes and will be exported as raw zip to {}", id, blob.len(), &output_file.display()),
-        ContentType::PNG(_) => {
-            let exported = z_lib::inflate(blob as &[u8])
+        ContentType::Z => {
+            let output_file = outpath.join(info.filename());
+            log.result(format!("slot {} is {} bytes and will be exported as raw zip to {}",
+                            info.id, blob.len(), output_file.display()));
+            log.emit(json!({"event": "check", "slot": info.id, "bytes": blob.len(),
+                            "file": output_file.display().to_string()}));
+        }
+        ContentType::Image(_, _) => {
+            let exported = codec::inflater()
+                .map(|inflater| encoding::decode(blob, inflater.as_ref()))
                 // then resolves the couple (format, inflated).
-                .and_then(|inflated| format_provider(inflated.len() as u32).map(|format| (format, inflated)))
-                .and_then(|(format, inflated)| {
-                    println!("slot {} is {} bytes ({} inflated) and will be exported as {}x{} image to {}",
-                             id, blob.len(), inflated.len(), format.w, format.h, &output_file.display());
-                    Ok(())
+                .and_then(|(encoding, inflated)| {
+                    let format = format_provider(inflated.len() as u32)?;
+                    let transform = format.transform()?;
+                    let (w, h) = transform.dimensions(format.w, format.h);
+                    let output_file = outpath.join(
+                        info.clone().with_transform(transform).with_encoding(encoding).filename());
+                    log.emit(json!({"event": "check", "slot": info.id, "bytes": blob.len(),
+                                    "encoding": encoding.to_string(), "decoded": inflated.len(),
+                                    "width": w, "height": h, "transform": transform.to_string(),
+                                    "file": output_file.display().to_string()}));
+                    Ok(format!("slot {} is {} bytes of {} ({} decoded) and will be exported as {}x{} image ({}) to {}",
+                               info.id, blob.len(), encoding, inflated.len(), w, h, transform, &output_file.display()))
                 });
-            if let Some(er) = exported.err() {
-                println!("{} slot {} ({} bytes) as an image : {}",
-                         warn("Cannot export"), id, blob.len(), warn(er.description()));
+            match exported {
+                Ok(line) => log.result(line),
+                Err(er) => {
+                    log.result(format!("{} slot {} ({} bytes) as an image : {}",
+                                    warn("Cannot export"), info.id, blob.len(), warn(&er)));
+                    log.emit(json!({"event": "slot_error", "slot": info.id, "error": er.to_string()}));
+                }
+            }
+        }
+        ContentType::Bin(_) | ContentType::Rgba(_) => {
+            match codec::inflater().map(|inflater| encoding::decode(blob, inflater.as_ref())) {
+                Ok((encoding, inflated)) => {
+                    let output_file = outpath.join(info.clone().with_encoding(encoding).filename());
+                    log.result(format!("slot {} is {} bytes of {} ({} decoded) and will be exported to {}",
+                                    info.id, blob.len(), encoding, inflated.len(), &output_file.display()));
+                    log.emit(json!({"event": "check", "slot": info.id, "bytes": blob.len(),
+                                    "encoding": encoding.to_string(), "decoded": inflated.len(),
+                                    "file": output_file.display().to_string()}));
+                }
+                Err(er) => {
+                    log.result(format!("{} slot {} ({} bytes) : {}",
+                                    warn("Cannot export"), info.id, blob.len(), warn(&er)));
+                    log.emit(json!({"event": "slot_error", "slot": info.id, "error": er.to_string()}));
+                }
             }
         }
     };
 }
 
-fn export_raw(info: &FileInfo, output_file: &PathBuf, blob: &Vec<u8>) -> Result<()> {
-    println!("storing slot {} ({} bytes) to {} as raw zip .",
-             data1(info.id),
-             data2(blob.len()),
-             emphasize1(output_file.display()));
+fn export_raw(info: &FileInfo, output_file: &Path, blob: &[u8], log: &mut Log) -> Result<()> {
+    log.say(format!("storing slot {} ({} bytes) to {} as raw zip .",
+                    data1(info.id),
+                    data2(blob.len()),
+                    emphasize1(output_file.display())));
+    log.emit(json!({"event": "slot", "slot": info.id, "bytes": blob.len(),
+                    "file": output_file.display().to_string()}));
     let mut f = File::create(output_file)?;
     f.write_all(blob)
 }
 
-fn export_png<F>(info: &FileInfo, output_file: &PathBuf, blob: &Vec<u8>, color_mode: &ColorMode, format_provider: F)
-                 -> Result<()>
+/// `measured` is the encoding and decoded size of the slot, when they are known already.
+fn export_image<F>(info: &FileInfo, outpath: &Path, blob: &[u8], measured: Option<(Encoding, u64)>,
+                   (color_mode, format): (&ColorMode, ImageFormat), format_provider: F, log: &mut Log) -> Result<()>
     where F: Fn(u32) -> Result<Format> {
-    // inflates the zip
-    z_lib::inflate(blob as &[u8])
-        // then resolves the couple (format, inflated).
-        .and_then(|inflated| format_provider(inflated.len() as u32).map(|format| (format, inflated)))
-        .and_then(|(format, inflated)| {
-            let file = File::create(output_file)?;
-            let file_writer = BufWriter::new(file);
-            println!("storing slot {} ({} bytes) to {} as {}x{} {} image.",
-                     data1(info.id),
-                     data2(blob.len()),
-                     emphasize1(output_file.display()),
-                     data3(format.w),
-                     data3(format.h),
-                     emphasize2(color_mode));
-            color_mode.write_png(file_writer, &inflated, format.w, format.h)
-        })
+    if format != ImageFormat::Png {
+        // only PNG is streamed: a single decode sizes and converts the slot.
+        let decoded = decode_slot(blob, color_mode, format_provider)?;
+        let output_file = image_file(info, outpath, blob, color_mode, &decoded, log);
+        return format.write_rgba(BufWriter::new(File::create(&output_file)?), &decoded.rgba, decoded.w, decoded.h);
+    }
+    let inflater = codec::inflater()?;
+    // sizes the raster first, without keeping it.
+    let (encoding, len) = measured.unwrap_or_else(|| stream::measure(blob, inflater.as_ref()));
+    let dimensions = format_provider(len as u32)?;
+    let transform = dimensions.transform()?;
+    if transform == Transform::Identity {
+        // straight logos are streamed row by row.
+        let (w, h) = (dimensions.w, dimensions.h);
+        let output_file = outpath.join(info.clone().with_encoding(encoding).filename());
+        log_image(info, &output_file, blob, encoding, color_mode, (w, h), log);
+        stream::blob_to_png(BufWriter::new(File::create(&output_file)?), blob, encoding, inflater.as_ref(),
+                            color_mode, w, h)
+    } else {
+        // the slot is already sized.
+        let decoded = decode_slot(blob, color_mode, |_sz| Ok(dimensions.clone()))?;
+        let output_file = image_file(info, outpath, blob, color_mode, &decoded, log);
+        format.write_rgba(BufWriter::new(File::create(&output_file)?), &decoded.rgba, decoded.w, decoded.h)
+    }
+}
+
+/// Names the image of a decoded slot: the transform and encoding are part of the name, so that repack can revert them.
+fn image_file(info: &FileInfo, outpath: &Path, blob: &[u8], color_mode: &ColorMode, decoded: &Decoded,
+              log: &mut Log) -> PathBuf {
+    let info = info.clone().with_transform(decoded.transform).with_encoding(decoded.encoding);
+    let output_file = outpath.join(info.filename());
+    log_image(&info, &output_file, blob, decoded.encoding, color_mode, (decoded.w, decoded.h), log);
+    output_file
+}
+
+fn log_image(info: &FileInfo, output_file: &Path, blob: &[u8], encoding: Encoding, color_mode: &ColorMode,
+             (w, h): (u32, u32), log: &mut Log) {
+    log.say(format!("storing slot {} ({} bytes, {}) to {} as {}x{} {} image.",
+                    data1(info.id),
+                    data2(blob.len()),
+                    data2(encoding),
+                    emphasize1(output_file.display()),
+                    data3(w),
+                    data3(h),
+                    emphasize2(color_mode)));
+    log.emit(json!({"event": "slot", "slot": info.id, "bytes": blob.len(), "encoding": encoding.to_string(),
+                    "file": output_file.display().to_string(), "width": w, "height": h,
+                    "color_mode": color_mode.to_string()}));
+}
+
+fn export_device(info: &FileInfo, outpath: &Path, blob: &[u8], log: &mut Log) -> Result<()> {
+    let (encoding, device) = encoding::decode(blob, codec::inflater()?.as_ref());
+    let info = info.clone().with_encoding(encoding);
+    let output_file = outpath.join(info.filename());
+    log.say(format!("storing slot {} ({} bytes, {}) to {} as {} bytes of device data.",
+                    data1(info.id),
+                    data2(blob.len()),
+                    data2(encoding),
+                    emphasize1(output_file.display()),
+                    data3(device.len())));
+    log.emit(json!({"event": "slot", "slot": info.id, "bytes": blob.len(), "encoding": encoding.to_string(),
+                    "file": output_file.display().to_string(), "device_bytes": device.len()}));
+    File::create(&output_file)?.write_all(&device)
+}
+
+fn export_rgba(info: &FileInfo, outpath: &Path, blob: &[u8], color_mode: &ColorMode, log: &mut Log) -> Result<()> {
+    let (encoding, device) = encoding::decode(blob, codec::inflater()?.as_ref());
+    let bpp = color_mode.bytes_per_pixel() as usize;
+    if device.len() % bpp != 0 {
+        return Err(IOError::new(ErrorKind::InvalidData,
+                                format!("{} bytes are not whole {} pixels", device.len(), color_mode)));
+    }
+    let pixels = (device.len() / bpp) as u32;
+    let info = info.clone().with_encoding(encoding);
+    let output_file = outpath.join(info.filename());
+    log.say(format!("storing slot {} ({} bytes, {}) to {} as {} {} pixels.",
+                    data1(info.id),
+                    data2(blob.len()),
+                    data2(encoding),
+                    emphasize1(output_file.display()),
+                    data3(pixels),
+                    emphasize2(color_mode)));
+    log.emit(json!({"event": "slot", "slot": info.id, "bytes": blob.len(), "encoding": encoding.to_string(),
+                    "file": output_file.display().to_string(), "pixels": pixels,
+                    "color_mode": color_mode.to_string()}));
+    let rgba = color_mode.device_to_rgba(&device, pixels, 1)?;
+    File::create(&output_file)?.write_all(&rgba)
 }
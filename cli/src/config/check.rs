@@ -0,0 +1,250 @@
+use serde_yaml;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Display;
+use std::fs;
+use std::io::Result;
+use std::path::Path;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+use mtklogo::{ColorMode, FileInfo};
+use mtklogo::utils::{check_level, Strategy};
+use mtklogo::utils::encoding::Encoding;
+use mtklogo::utils::image::Transform;
+use super::{Config, Format};
+
+/// Versions of the configuration format this program reads.
+pub const SUPPORTED_VERSIONS: [&str; 1] = ["1.1"];
+
+/// Something wrong in a configuration file.
+pub struct Problem {
+    /// Line in the YAML file, when it can be told.
+    pub line: Option<usize>,
+    pub profile: Option<String>,
+    pub message: String,
+}
+
+/// The line is left out, it's told along with the file.
+impl Display for Problem {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref profile) = self.profile {
+            write!(fmt, "profile '{}': ", profile)?;
+        }
+        fmt.write_str(&self.message)
+    }
+}
+
+/// Checks a configuration file. Profiles may extend profiles of other files: `all` is the
+/// configuration they are resolved in.
+pub fn check_file(path: &Path, all: &Config) -> Result<Vec<Problem>> {
+    let text = fs::read_to_string(path)?;
+    // the lines of the YAML nodes, serde does not keep them.
+    let mut lines = LineBuilder::default();
+    if let Err(e) = Parser::new(text.chars()).load(&mut lines, false) {
+        return Ok(vec![Problem { line: Some(e.marker().line()), profile: None, message: format!("{}", e) }]);
+    }
+    let root = match lines.root {
+        Some(root) => root,
+        None => return Ok(vec![Problem { line: None, profile: None, message: "empty file".to_string() }]),
+    };
+    let config: Config = match serde_yaml::from_str(&text) {
+        Ok(config) => config,
+        Err(e) => return Ok(vec![Problem { line: None, profile: None, message: format!("{}", e) }]),
+    };
+    let mut problems = Vec::new();
+    if !config.version.is_empty() && !SUPPORTED_VERSIONS.contains(&config.version.as_str()) {
+        problems.push(Problem {
+            line: root.get("version").map(|node| node.line),
+            profile: None,
+            message: format!("version '{}' is not supported, expected one of {:?}", config.version, SUPPORTED_VERSIONS),
+        });
+    }
+    let profiles = root.get("profiles");
+    // first line declaring a name or alias.
+    let mut names: HashMap<&str, usize> = HashMap::new();
+    for (i, profile) in config.profiles.iter().enumerate() {
+        let node = profiles.and_then(|profiles| profiles.at(i));
+        let mut report = |key: Option<&Node>, message: String| problems.push(Problem {
+            line: key.or(node).map(|node| node.line),
+            profile: Some(profile.name.clone()),
+            message,
+        });
+        let get = |key: &str| node.and_then(|node| node.get(key));
+        // names and aliases must tell a single profile.
+        let aliases = profile.alias.iter().flatten().enumerate()
+            .map(|(j, alias)| (alias, get("alias").and_then(|node| node.at(j))));
+        for (name, name_node) in Some((&profile.name, get("name"))).into_iter().chain(aliases) {
+            match names.get(name.as_str()) {
+                Some(first) => report(name_node, format!("'{}' is already declared at line {}", name, first)),
+                None => {
+                    names.insert(name, name_node.or(node).map_or(0, |node| node.line));
+                }
+            }
+        }
+        if profile.color_model.is_empty() {
+            if profile.extends.is_none() {
+                report(None, "color_model is missing".to_string());
+            }
+        } else if let Err(e) = ColorMode::by_name(&profile.color_model) {
+            report(get("color_model"), format!("color_model: {}", e));
+        }
+        if let Err(e) = check_transform(&profile.transform) {
+            report(get("transform"), e);
+        }
+        if let Some(level) = profile.level {
+            if let Err(e) = check_level(level) {
+                report(get("level"), format!("level: {}", e));
+            }
+        }
+        if let Some(ref strategy) = profile.strategy {
+            if let Err(e) = Strategy::by_name(strategy) {
+                report(get("strategy"), format!("strategy: {}", e));
+            }
+        }
+        if profile.extends.is_some() {
+            if let Err(e) = all.profile(&profile.name) {
+                report(get("extends"), format!("extends: {}", e));
+            }
+        }
+        if let Some(ref encoding) = profile.fingerprint.as_ref().and_then(|f| f.encoding.clone()) {
+            if let Err(e) = Encoding::by_name(encoding) {
+                report(get("fingerprint"), format!("fingerprint encoding: {}", e));
+            }
+        }
+        // a slot's size is a 32 bits number.
+        let bpp = all.profile(&profile.name).ok()
+            .and_then(|resolved| ColorMode::by_name(&resolved.color_model).ok())
+            .map_or(1, |mode| mode.bytes_per_pixel()) as u64;
+        for (j, format) in profile.formats.iter().enumerate() {
+            let format_node = get("formats").and_then(|node| node.at(j));
+            if format.w == 0 || format.h == 0 {
+                report(format_node, format!("format {}x{} has a zero dimension", format.w, format.h));
+            }
+            if format.pixels() * bpp > u32::MAX as u64 {
+                report(format_node, format!("format {}x{} is too large for a slot", format.w, format.h));
+            }
+            if let Err(e) = check_transform(&format.transform) {
+                report(format_node, e);
+            }
+            // sizes are looked up by pixel count, the first format of a given count hides the others.
+            if let Some(first) = profile.formats[..j].iter().find(|f| f.pixels() == format.pixels()) {
+                report(format_node, ambiguity(first, format));
+            }
+        }
+        for (j, slot) in profile.slots.iter().flatten().enumerate() {
+            let slot_node = get("slots").and_then(|node| node.at(j));
+            if let Err(e) = slot.index.bounds() {
+                report(slot_node, format!("{}", e));
+            }
+            if let Some(ref name) = slot.name {
+                if let Err(e) = FileInfo::check_name(name) {
+                    report(slot_node, format!("{}", e));
+                }
+            }
+            if slot.w == 0 || slot.h == 0 {
+                report(slot_node, format!("slot {}x{} has a zero dimension", slot.w, slot.h));
+            }
+            let slot_bpp = match slot.color_model {
+                Some(ref color_model) => match ColorMode::by_name(color_model) {
+                    Ok(mode) => mode.bytes_per_pixel() as u64,
+                    Err(e) => {
+                        report(slot_node, format!("color_model: {}", e));
+                        bpp
+                    }
+                },
+                None => bpp,
+            };
+            if slot.pixels() * slot_bpp > u32::MAX as u64 {
+                report(slot_node, format!("slot {}x{} is too large", slot.w, slot.h));
+            }
+            if let Err(e) = check_transform(&slot.transform) {
+                report(slot_node, e);
+            }
+        }
+    }
+    Ok(problems)
+}
+
+fn check_transform(transform: &Option<String>) -> ::std::result::Result<(), String> {
+    match transform {
+        Some(ref name) => Transform::by_name(name).map(|_| ()).map_err(|e| format!("transform: {}", e)),
+        None => Ok(()),
+    }
+}
+
+fn ambiguity(first: &Format, format: &Format) -> String {
+    if (first.w, first.h) == (format.w, format.h) {
+        format!("format {}x{} is declared twice", format.w, format.h)
+    } else {
+        format!("formats {}x{} and {}x{} both have {} pixels, their sizes cannot tell them apart",
+                first.w, first.h, format.w, format.h, format.pixels())
+    }
+}
+
+/// A YAML node, along with the line it starts on.
+struct Node {
+    line: usize,
+    value: Value,
+}
+
+enum Value {
+    Scalar,
+    Sequence(Vec<Node>),
+    Mapping(Vec<(String, Node)>),
+}
+
+impl Node {
+    fn get(&self, key: &str) -> Option<&Node> {
+        match self.value {
+            Value::Mapping(ref entries) => entries.iter().find(|(k, _)| k == key).map(|(_, node)| node),
+            _ => None,
+        }
+    }
+    fn at(&self, index: usize) -> Option<&Node> {
+        match self.value {
+            Value::Sequence(ref items) => items.get(index),
+            _ => None,
+        }
+    }
+}
+
+/// Builds the tree of nodes from the parser's events.
+#[derive(Default)]
+struct LineBuilder {
+    /// Open sequences and mappings, with the pending key of a mapping.
+    stack: Vec<(Node, Option<String>)>,
+    root: Option<Node>,
+}
+
+impl LineBuilder {
+    fn push(&mut self, node: Node, scalar: Option<String>) {
+        match self.stack.last_mut() {
+            None => self.root = Some(node),
+            Some((Node { value: Value::Sequence(ref mut items), .. }, _)) => items.push(node),
+            Some((Node { value: Value::Mapping(ref mut entries), .. }, ref mut key)) => match key.take() {
+                Some(key) => entries.push((key, node)),
+                // complex keys are not used by the configuration.
+                None => *key = Some(scalar.unwrap_or_default()),
+            },
+            Some(_) => (),
+        }
+    }
+}
+
+impl MarkedEventReceiver for LineBuilder {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        let line = mark.line();
+        match event {
+            Event::Scalar(value, ..) => self.push(Node { line, value: Value::Scalar }, Some(value)),
+            Event::Alias(_) => self.push(Node { line, value: Value::Scalar }, None),
+            Event::SequenceStart(_) => self.stack.push((Node { line, value: Value::Sequence(Vec::new()) }, None)),
+            Event::MappingStart(_) => self.stack.push((Node { line, value: Value::Mapping(Vec::new()) }, None)),
+            Event::SequenceEnd | Event::MappingEnd => {
+                if let Some((node, _)) = self.stack.pop() {
+                    self.push(node, None);
+                }
+            }
+            _ => (),
+        }
+    }
+}
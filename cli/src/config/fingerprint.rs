@@ -0,0 +1,185 @@
+use std::cmp;
+use std::io::Result;
+use mtklogo::LogoImage;
+use mtklogo::utils::{codec, stream};
+use mtklogo::utils::encoding::Encoding;
+use super::{Config, Profile};
+
+/// What a logo image of a device looks like, to tell its profile without `--profile`.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Fingerprint {
+    /// Number of slots.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slots: Option<usize>,
+    /// Decoded sizes of the first slots, in bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sizes: Option<Vec<u64>>,
+    /// How slots are stored: "zlib", "gzip", "lz4" or "raw".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    /// Size of the slots and their table, as the header tells it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_size: Option<u32>,
+}
+
+/// What is read from the header of a logo image, and measured of each of its slots.
+pub struct Traits {
+    block_size: u32,
+    slots: Vec<(Encoding, u64)>,
+}
+
+impl Traits {
+    pub fn measure(image: &LogoImage) -> Result<Traits> {
+        let inflater = codec::inflater()?;
+        let slots = image.blobs.iter().map(|blob| stream::measure(blob, inflater.as_ref())).collect();
+        Ok(Traits { block_size: image.table.block_size, slots })
+    }
+
+    /// Encoding and decoded size of a slot, as measured.
+    pub fn slot(&self, id: usize) -> Option<(Encoding, u64)> {
+        self.slots.get(id).cloned()
+    }
+}
+
+// weights of the scores: declared slots are sized exactly, they tell more than formats.
+const FORMATS_WEIGHT: f64 = 1.0;
+const SLOTS_WEIGHT: f64 = 2.0;
+const FINGERPRINT_SLOTS_WEIGHT: f64 = 1.0;
+const FINGERPRINT_SIZES_WEIGHT: f64 = 2.0;
+const FINGERPRINT_ENCODING_WEIGHT: f64 = 1.0;
+const FINGERPRINT_BLOCK_SIZE_WEIGHT: f64 = 2.0;
+/// Evidence a profile is assumed to lack: the less a profile declares, the lower its confidence,
+/// a profile which sizes every slot from its formats alone scores 83%.
+const MISSING_WEIGHT: f64 = 0.2;
+
+impl Profile {
+    /// How much a logo image looks like this profile's, from 0 to 1.
+    /// Each thing the profile declares gives a score, the confidence is their weighted average,
+    /// lowered for the profiles which declare little, so that the more specific one wins.
+    pub fn confidence(&self, traits: &Traits) -> f64 {
+        let count = traits.slots.len();
+        let sized = |id: usize| traits.slots.get(id)
+            .is_some_and(|&(_, size)| size <= u32::MAX as u64 && self.slot_format(id, size as u32, false).is_ok());
+        let mut scores: Vec<(f64, f64)> = Vec::new();
+        if count > 0 {
+            let hits = (0..count).filter(|id| sized(*id)).count();
+            scores.push((hits as f64 / count as f64, FORMATS_WEIGHT));
+        }
+        let declared: Vec<(usize, usize)> = self.slots.iter().flatten()
+            .filter_map(|slot| slot.index.bounds().ok())
+            .collect();
+        if !declared.is_empty() {
+            // only the slots the image has are looked at, the others are misses.
+            let total: f64 = declared.iter().map(|&(first, last)| (last - first) as f64 + 1.0).sum();
+            let hits: usize = declared.iter()
+                .filter(|&&(first, _)| first < count)
+                .map(|&(first, last)| (first..=cmp::min(last, count - 1)).filter(|id| sized(*id)).count())
+                .sum();
+            scores.push((hits as f64 / total, SLOTS_WEIGHT));
+        }
+        if let Some(ref fingerprint) = self.fingerprint {
+            if let Some(slots) = fingerprint.slots {
+                scores.push((if slots == count { 1.0 } else { 0.0 }, FINGERPRINT_SLOTS_WEIGHT));
+            }
+            if let Some(ref sizes) = fingerprint.sizes {
+                if !sizes.is_empty() {
+                    let hits = sizes.iter().enumerate()
+                        .filter(|(id, size)| traits.slots.get(*id).is_some_and(|&(_, s)| s == **size))
+                        .count();
+                    scores.push((hits as f64 / sizes.len() as f64, FINGERPRINT_SIZES_WEIGHT));
+                }
+            }
+            if let Some(ref name) = fingerprint.encoding {
+                if count > 0 {
+                    let encoding = Encoding::by_name(name).ok();
+                    let hits = traits.slots.iter().filter(|&&(e, _)| Some(e) == encoding).count();
+                    scores.push((hits as f64 / count as f64, FINGERPRINT_ENCODING_WEIGHT));
+                }
+            }
+            if let Some(block_size) = fingerprint.block_size {
+                scores.push((if block_size == traits.block_size { 1.0 } else { 0.0 }, FINGERPRINT_BLOCK_SIZE_WEIGHT));
+            }
+        }
+        let weight: f64 = scores.iter().map(|(_, w)| w).sum();
+        if weight == 0.0 {
+            return 0.0;
+        }
+        let score = scores.iter().map(|(s, w)| s * w).sum::<f64>() / weight;
+        score * weight / (weight + MISSING_WEIGHT)
+    }
+}
+
+impl Config {
+    /// Scores every profile against a logo image, the most likely first.
+    /// Profiles which cannot be resolved are left out.
+    pub fn rank(&self, traits: &Traits) -> Vec<(Profile, f64)> {
+        let mut ranked: Vec<(Profile, f64)> = self.profiles.iter()
+            .filter_map(|declared| self.profile(&declared.name).ok())
+            .map(|profile| {
+                let confidence = profile.confidence(traits);
+                (profile, confidence)
+            })
+            .collect();
+        ranked.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(cmp::Ordering::Equal));
+        ranked
+    }
+}
+
+#[cfg(test)]
+fn test_traits(block_size: u32, slots: &[(Encoding, u64)]) -> Traits {
+    Traits { block_size, slots: slots.to_vec() }
+}
+
+#[cfg(test)]
+const TEST_CONFIG: &str = r#"
+profiles:
+  - { name: default, color_model: rgb565le, formats: [ { w: 720, h: 1280 }, { w: 32, h: 32 } ] }
+  - { name: small, color_model: rgb565le, formats: [ { w: 480, h: 854 } ] }
+  - name: device
+    extends: default
+    slots: [ { index: 0, name: boot, w: 720, h: 1280 } ]
+    fingerprint: { slots: 2, sizes: [ 1843200 ], encoding: zlib, block_size: 4096 }
+"#;
+
+#[test]
+fn test_confidence() {
+    let config: Config = ::serde_yaml::from_str(TEST_CONFIG).unwrap();
+    let traits = test_traits(4096, &[(Encoding::Zlib, 1843200), (Encoding::Zlib, 2048)]);
+    let confidence = |name: &str| config.profile(name).unwrap().confidence(&traits);
+    // formats alone can be trusted.
+    let default = confidence("default");
+    assert!(default > 0.8 && default < 0.9, "{}", default);
+    assert_eq!(confidence("small"), 0.0);
+    assert!(confidence("device") > default + 0.1);
+    // half the slots are sized.
+    let half = config.profile("default").unwrap().confidence(&test_traits(4096, &[(Encoding::Zlib, 1843200), (Encoding::Zlib, 7)]));
+    assert!((half - default / 2.0).abs() < 1e-9);
+    // the header and encoding disagree.
+    let other = test_traits(8192, &[(Encoding::Lz4, 1843200), (Encoding::Lz4, 2048)]);
+    assert!(config.profile("device").unwrap().confidence(&other) < default);
+    assert_eq!(config.profile("default").unwrap().confidence(&test_traits(0, &[])), 0.0);
+}
+
+#[test]
+fn test_rank() {
+    let config: Config = ::serde_yaml::from_str(TEST_CONFIG).unwrap();
+    let names = |traits: &Traits| config.rank(traits).into_iter().map(|(p, _)| p.name).collect::<Vec<String>>();
+    assert_eq!(names(&test_traits(4096, &[(Encoding::Zlib, 1843200), (Encoding::Zlib, 2048)])),
+               vec!["device", "default", "small"]);
+    assert_eq!(names(&test_traits(1024, &[(Encoding::Raw, 819840)])), vec!["small", "default", "device"]);
+}
+
+#[test]
+fn test_confidence_of_huge_ranges() {
+    let config: Config = ::serde_yaml::from_str(r#"
+profiles:
+  - { name: default, color_model: rgb565le, formats: [ { w: 720, h: 1280 } ] }
+  - name: huge
+    extends: default
+    slots: [ { index: "0..18446744073709551615", w: 720, h: 1280 } ]
+"#).unwrap();
+    let traits = test_traits(4096, &[(Encoding::Zlib, 1843200), (Encoding::Zlib, 1843200)]);
+    // slots the image lacks are misses, without being enumerated.
+    let huge = config.profile("huge").unwrap().confidence(&traits);
+    assert!(huge < config.profile("default").unwrap().confidence(&traits), "{}", huge);
+}
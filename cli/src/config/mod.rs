@@ -1,14 +1,28 @@
 use serde_yaml;
 use std::convert::From;
 use std::env;
-use std::error::Error;
-use std::fs::File;
-use std::io::{Error as IOError, ErrorKind, Result};
+use std::fs::{self, File};
+use std::io::{Error as IOError, ErrorKind, Result, Write};
 use std::path::{Path, PathBuf};
-use mtklogo::ColorMode;
+use mtklogo::{ColorMode, FileInfo};
+use mtklogo::utils::{check_level, Strategy, MAX_LEVEL};
+use mtklogo::utils::image::Transform;
+
+pub use self::check::check_file;
+pub use self::fingerprint::{Fingerprint, Traits};
+
+// configuration diagnostics.
+mod check;
+// profile selection.
+mod fingerprint;
+
+/// The sample configuration, the lowest priority one.
+const BUILTIN_CONFIG: &str = include_str!("../../resources/bin/mtklogo.yaml");
 
 #[derive(Deserialize, Serialize)]
 pub struct Config {
+    /// May be left out by the fragments of a `conf.d` directory.
+    #[serde(default)]
     pub version: String,
     pub profiles: Vec<Profile>,
 }
@@ -16,22 +30,141 @@ pub struct Config {
 #[derive(Deserialize, Serialize, Clone)]
 pub struct Profile {
     pub name: String,
+    /// Name of the profile this one inherits from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    /// May be left out by a profile which extends another one.
+    #[serde(default)]
     pub color_model: String,
+    /// Aliases are not inherited.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub alias: Option<Vec<String>>,
+    /// Default transform from device raster to upright image.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<String>,
+    /// Compression level used when repacking (0-9).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<u32>,
+    /// Compression strategy used when repacking.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<String>,
+    /// Slots whose dimensions are known, they are not guessed from their size.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slots: Option<Vec<Slot>>,
+    /// Formats added to the inherited ones, they come first.
+    #[serde(default)]
     pub formats: Vec<Format>,
+    /// Inherited formats to leave out, matched by width and height.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remove_formats: Option<Vec<Format>>,
+    /// Tells this profile's logo images, it's not inherited.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<Fingerprint>,
 }
 
 impl Profile {
     pub fn with_color_model(self, color_model: String) -> Profile {
-        return Profile { name: self.name, color_model, alias: self.alias, formats: self.formats };
+        Profile { color_model, ..self }
+    }
+    pub fn with_transform(self, transform: String) -> Profile {
+        Profile { transform: Some(transform), ..self }
+    }
+    /// Merges this profile over the one it extends: what it declares prevails,
+    /// its formats and slots come before the inherited ones.
+    fn inherit(self, parent: Profile) -> Profile {
+        let removed = self.remove_formats.unwrap_or_default();
+        let mut formats = self.formats;
+        formats.extend(parent.formats.into_iter()
+            .filter(|f| !removed.iter().any(|r| r.w == f.w && r.h == f.h)));
+        let slots = match (self.slots, parent.slots) {
+            (Some(mut slots), Some(inherited)) => {
+                slots.extend(inherited);
+                Some(slots)
+            }
+            (slots, inherited) => slots.or(inherited),
+        };
+        Profile {
+            name: self.name,
+            extends: None,
+            color_model: if self.color_model.is_empty() { parent.color_model } else { self.color_model },
+            alias: self.alias,
+            transform: self.transform.or(parent.transform),
+            level: self.level.or(parent.level),
+            strategy: self.strategy.or(parent.strategy),
+            slots,
+            formats,
+            remove_formats: None,
+            fingerprint: self.fingerprint,
+        }
+    }
+    /// Resolves the compression settings of this profile (best compression by default).
+    pub fn compression(&self) -> Result<(u32, Strategy)> {
+        let level = check_level(self.level.unwrap_or(MAX_LEVEL))?;
+        let strategy = match self.strategy {
+            Some(ref name) => Strategy::by_name(name)?,
+            None => Strategy::Default,
+        };
+        Ok((level, strategy))
+    }
+    /// Resolves the default transform of this profile (none by default).
+    pub fn transform(&self) -> Result<Transform> {
+        transform_by_name(&self.transform)
+    }
+    /// Finds the entry of a slot, with its own name when it's part of a range.
+    pub fn slot(&self, id: usize) -> Result<Option<Slot>> {
+        for slot in self.slots.iter().flatten() {
+            let (first, last) = slot.index.bounds()?;
+            if first <= id && id <= last {
+                if let Some(ref name) = slot.name {
+                    FileInfo::check_name(name).map_err(
+                        |e| IOError::new(ErrorKind::InvalidData, format!("profile '{}': {}", self.name, e)))?;
+                }
+                let name = match slot.name {
+                    Some(ref name) if first != last => Some(format!("{}_{}", name, id - first)),
+                    ref name => name.clone(),
+                };
+                return Ok(Some(Slot { index: SlotIndex::One(id), name, ..slot.clone() }));
+            }
+        }
+        Ok(None)
+    }
+    /// Resolves the color mode of a slot: its own, or the profile's.
+    pub fn slot_color_mode<'a>(&self, id: usize) -> Result<&'a ColorMode> {
+        match self.slot(id)? {
+            Some(Slot { color_model: Some(ref name), .. }) => ColorMode::by_name(name),
+            _ => ColorMode::by_name(&self.color_model),
+        }
+    }
+    /// Resolves the format of a slot: its own entry first, then guessed from its size.
+    /// Declared dimensions are not flipped.
+    pub fn slot_format(&self, id: usize, size: u32, flip: bool) -> Result<Format> {
+        match self.slot(id)? {
+            Some(slot) => {
+                let mtk_color_model = self.slot_color_mode(id)?;
+                let expected = slot.pixels() * mtk_color_model.bytes_per_pixel() as u64;
+                if expected != size as u64 {
+                    return Err(IOError::new(ErrorKind::InvalidData, format!(
+                        "slot {} is declared {}x{} {} ({} bytes) in profile '{}', but holds {} bytes",
+                        id, slot.w, slot.h, mtk_color_model, expected, self.name, size)));
+                }
+                Ok(Format {
+                    w: slot.w,
+                    h: slot.h,
+                    t: slot.name,
+                    transform: slot.transform.or_else(|| self.transform.clone()),
+                })
+            }
+            None => self.guess_format(size, flip),
+        }
     }
     pub fn guess_format(&self, size: u32, flip: bool) -> Result<Format> {
         let mtk_color_model = ColorMode::by_name(&self.color_model)?;
         let bpp = mtk_color_model.bytes_per_pixel();
         let pixels = size / bpp;
         let o = self.formats.iter()
-            .find(|f| f.w * f.h == pixels)
-            .map(|f| { f.clone() });
+            .find(|f| f.pixels() == pixels as u64)
+            // a format's own transform prevails on profile's default.
+            .map(|f| Format { transform: f.transform.clone().or_else(|| self.transform.clone()), ..f.clone() });
         match o {
             Some(f) => {
                 if flip {
@@ -47,11 +180,63 @@ impl Profile {
     }
 }
 
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Slot {
+    /// Slot index, or an inclusive range of indexes such as "38..47".
+    pub index: SlotIndex,
+    /// Name of the slot, e.g. "boot". The slots of a range are numbered: "charging_0", "charging_1"...
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub w: u32,
+    pub h: u32,
+    /// Color model of the slot, the profile's by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_model: Option<String>,
+    /// Transform from device raster to upright image, the profile's by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<String>,
+}
+
+impl Slot {
+    /// Pixel count, it does not overflow.
+    pub fn pixels(&self) -> u64 {
+        self.w as u64 * self.h as u64
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum SlotIndex {
+    One(usize),
+    Range(String),
+}
+
+impl SlotIndex {
+    /// First and last indexes.
+    pub fn bounds(&self) -> Result<(usize, usize)> {
+        match self {
+            SlotIndex::One(index) => Ok((*index, *index)),
+            SlotIndex::Range(range) => {
+                let bounds: Vec<Option<usize>> = range.split("..").map(|bound| bound.trim().parse().ok()).collect();
+                match bounds[..] {
+                    [Some(first), Some(last)] if first <= last => Ok((first, last)),
+                    _ => Err(IOError::new(ErrorKind::InvalidData,
+                                          format!("slot index '{}' is not a number nor a range like '38..47'", range)))
+                }
+            }
+        }
+    }
+}
+
 #[derive(Clone, Deserialize, Serialize)]
 pub struct Format {
     pub w: u32,
     pub h: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub t: Option<String>,
+    /// Transform from device raster to upright image, for images of this format.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<String>,
 }
 
 impl Format {
@@ -64,46 +249,106 @@ impl Format {
             w: self.h,
             h: self.w,
             t: flipped_title,
+            transform: self.transform.clone(),
         }
     }
+
+    /// Resolves the transform to apply to this format (none by default).
+    pub fn transform(&self) -> Result<Transform> {
+        transform_by_name(&self.transform)
+    }
+
+    /// Pixel count, it does not overflow.
+    pub fn pixels(&self) -> u64 {
+        self.w as u64 * self.h as u64
+    }
+}
+
+fn transform_by_name(name: &Option<String>) -> Result<Transform> {
+    match name {
+        Some(ref name) => Transform::by_name(name),
+        None => Ok(Transform::Identity),
+    }
 }
 
 
 impl Config {
-    const GLOBAL_CONFIG: &'static str = "/etc/mtklogo.yaml";
+    const ENV_CONFIG: &'static str = "MTKLOGO_CONFIG";
+    const GLOBAL_DIR: &'static str = "/etc";
     const RELATIVE_CONFIG: &'static str = "mtklogo.yaml";
+    const FRAGMENTS_DIR: &'static str = "conf.d";
 
-    /// Resolves configuration path, in this order:
-    /// - `"mtklogo.yaml"` in $HOME/.config
-    /// - `"mtklogo.yaml"` in /etc
-    /// - `"mtklogo.yaml"` in program's installation directory
-    fn config_path() -> Result<(PathBuf, File)> {
-        // in home directory?
-        let home_config = {
-            #[allow(deprecated)] // hey i'm fine with a basic 'env::$HOME' behaviour.
-            let mut home = env::home_dir().ok_or(IOError::new(ErrorKind::NotFound, "No home directory."))?;
-            home.push(".config");
-            home.push(Self::RELATIVE_CONFIG);
-            File::open(home.as_path()).map(|f| (home, f))
-        };
+    /// Lists configuration files, from the highest priority to the lowest:
+    /// - the file named by $MTKLOGO_CONFIG
+    /// - `"mtklogo/conf.d/*.yaml"` then `"mtklogo.yaml"` in $XDG_CONFIG_HOME ($HOME/.config by default)
+    /// - `"mtklogo/conf.d/*.yaml"` then `"mtklogo.yaml"` in /etc
+    /// - `"conf.d/*.yaml"` then `"mtklogo.yaml"` in program's installation directory
+    ///
+    /// Fragments of a `conf.d` directory are taken in reverse name order, so that the last one prevails.
+    pub fn config_paths() -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        // named by the user?
+        if let Some(path) = env::var_os(Self::ENV_CONFIG) {
+            let path = PathBuf::from(path);
+            if !path.is_file() {
+                return Err(IOError::new(ErrorKind::NotFound, format!(
+                    "{} names {}, which is not a file", Self::ENV_CONFIG, path.display())));
+            }
+            paths.push(path);
+        }
+        // in user's config directory?
+        if let Some(dir) = Self::user_dir() {
+            Self::push_layer(&mut paths, &dir, &dir.join("mtklogo").join(Self::FRAGMENTS_DIR))?;
+        }
         // in /etc?
-        let etc_config = {
-            File::open(Config::GLOBAL_CONFIG).map(|f| (PathBuf::from(Config::GLOBAL_CONFIG), f))
-        };
+        let etc_dir = Path::new(Self::GLOBAL_DIR);
+        Self::push_layer(&mut paths, etc_dir, &etc_dir.join("mtklogo").join(Self::FRAGMENTS_DIR))?;
         // along with the executable?
-        let shipped_config = {
-            let self_dir = env::current_exe()?;
-            let parent = self_dir.parent()
-                .ok_or(IOError::new(ErrorKind::NotFound, "Current executable is not inside a folder"))?; /* seriously ? */
-            let mut self_config = PathBuf::from(parent);
-            self_config.push(Self::RELATIVE_CONFIG);
-            File::open(self_config.as_path()).map(|f| (self_config, f))
-        };
-        home_config
-            .or_else(|_| etc_config)
-            .or_else(|_| shipped_config)
-            .map_err(|_| IOError::new(ErrorKind::NotFound,
-                                      "`mtklogo.yaml` configuration not found, please provide one."))
+        let self_exe = env::current_exe()?;
+        if let Some(self_dir) = self_exe.parent() {
+            Self::push_layer(&mut paths, self_dir, &self_dir.join(Self::FRAGMENTS_DIR))?;
+        }
+        Ok(paths)
+    }
+
+    /// $XDG_CONFIG_HOME, or $HOME/.config.
+    fn user_dir() -> Option<PathBuf> {
+        match env::var_os("XDG_CONFIG_HOME").map(PathBuf::from) {
+            Some(ref dir) if dir.is_absolute() => Some(dir.clone()),
+            _ => {
+                #[allow(deprecated)] // hey i'm fine with a basic 'env::$HOME' behaviour.
+                let home = env::home_dir();
+                home.map(|home| home.join(".config"))
+            }
+        }
+    }
+
+    /// Where the user's profile fragments go.
+    pub fn user_fragments_dir() -> Result<PathBuf> {
+        Self::user_dir()
+            .map(|dir| dir.join("mtklogo").join(Self::FRAGMENTS_DIR))
+            .ok_or_else(|| IOError::new(ErrorKind::NotFound, "No home directory."))
+    }
+
+    /// Adds the fragments, then the `mtklogo.yaml` of a directory, as far as they exist.
+    fn push_layer(paths: &mut Vec<PathBuf>, dir: &Path, fragments_dir: &Path) -> Result<()> {
+        if fragments_dir.is_dir() {
+            let mut fragments = Vec::new();
+            for entry in fs::read_dir(fragments_dir)? {
+                let path = entry?.path();
+                let is_yaml = path.extension().is_some_and(|ext| ext == "yaml" || ext == "yml");
+                if is_yaml && path.is_file() {
+                    fragments.push(path);
+                }
+            }
+            fragments.sort();
+            paths.extend(fragments.into_iter().rev());
+        }
+        let config = dir.join(Self::RELATIVE_CONFIG);
+        if config.is_file() {
+            paths.push(config);
+        }
+        Ok(())
     }
 
     fn wrap_read(path: &Path, file: File) -> Result<Config> {
@@ -111,7 +356,7 @@ impl Config {
         config.map_err(
             |e| IOError::new(ErrorKind::InvalidData,
                              format!(
-                                 "could not read config {} -> '{}'", path.display(), e.description())))
+                                 "could not read config {} -> '{}'", path.display(), e)))
     }
 
     pub fn from_file(path: &Path) -> Result<Config> {
@@ -119,7 +364,155 @@ impl Config {
         Self::wrap_read(path, file)
     }
 
+    /// Merges configurations, given from the highest priority to the lowest.
+    pub fn merge(configs: Vec<Config>) -> Option<Config> {
+        let mut configs = configs.into_iter();
+        let mut merged = configs.next()?;
+        for config in configs {
+            if merged.version.is_empty() {
+                merged.version = config.version;
+            }
+            for profile in config.profiles {
+                if !merged.profiles.iter().any(|p| p.name == profile.name) {
+                    merged.profiles.push(profile);
+                }
+            }
+        }
+        Some(merged)
+    }
+
+    /// Loads every configuration file, then the built-in profiles, which come last:
+    /// a profile hides the profiles of the same name in lower priority files.
     pub fn load() -> Result<Config> {
-        Config::config_path().and_then(|(path, file)| Self::wrap_read(path.as_path(), file))
+        let mut configs = Vec::new();
+        for path in Self::config_paths()? {
+            configs.push(Self::from_file(&path)?);
+        }
+        configs.push(Self::builtin()?);
+        Self::merge(configs).ok_or_else(|| IOError::new(ErrorKind::NotFound, "no configuration."))
     }
-}
\ No newline at end of file
+
+    /// The profiles compiled in this program, from the sample configuration.
+    pub fn builtin() -> Result<Config> {
+        serde_yaml::from_str(BUILTIN_CONFIG).map_err(
+            |e| IOError::new(ErrorKind::InvalidData, format!("could not read built-in config -> '{}'", e)))
+    }
+
+    /// Writes the built-in configuration to a file, to be edited.
+    pub fn write_builtin(path: &Path) -> Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(BUILTIN_CONFIG.as_bytes())
+    }
+
+    /// Where the user's configuration goes.
+    pub fn user_config_path() -> Result<PathBuf> {
+        Self::user_dir()
+            .map(|dir| dir.join(Self::RELATIVE_CONFIG))
+            .ok_or_else(|| IOError::new(ErrorKind::NotFound, "No home directory."))
+    }
+
+    /// Finds a profile by its name or one of its aliases, merged with the profiles it extends.
+    pub fn profile(&self, name: &str) -> Result<Profile> {
+        let profile = self.resolve(name, &mut Vec::new())?;
+        if profile.color_model.is_empty() {
+            return Err(IOError::new(ErrorKind::InvalidData,
+                                    format!("profile '{}' has no color_model", name)));
+        }
+        Ok(profile)
+    }
+
+    /// Merges a profile over its parent, itself resolved first: each level removes formats
+    /// from everything it inherits. `lineage` lists the profiles walked down to this one.
+    fn resolve(&self, name: &str, lineage: &mut Vec<String>) -> Result<Profile> {
+        let profile = self.declared_profile(name)?;
+        let looping = lineage.contains(&profile.name);
+        lineage.push(profile.name.clone());
+        if looping {
+            return Err(IOError::new(ErrorKind::InvalidData, format!(
+                "profile '{}' extends itself: '{}'", lineage[0], lineage.join("' -> '"))));
+        }
+        match profile.extends.clone() {
+            Some(parent_name) => {
+                let parent = self.resolve(&parent_name, lineage)?;
+                Ok(profile.inherit(parent))
+            }
+            None => Ok(profile),
+        }
+    }
+
+    /// Finds a profile as declared, by its name or one of its aliases.
+    fn declared_profile(&self, name: &str) -> Result<Profile> {
+        /// does profile have this name or this alias?
+        fn match_name_or_alias(profile: &Profile, name: &str) -> bool {
+            if profile.name.eq(name) {
+                true
+            } else {
+                match profile.alias {
+                    None => false,
+                    Some(ref aliases) => aliases.contains(&name.to_string())
+                }
+            }
+        }
+        match self.profiles.iter().find(|profile| match_name_or_alias(profile, name)) {
+            Some(p) => Ok(p.clone()),
+            None => Err(IOError::new(ErrorKind::InvalidData,
+                                     format!("profile '{}' is not declared in configuration file", name)))
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_config(yaml: &str) -> Config {
+    serde_yaml::from_str(yaml).unwrap()
+}
+
+#[cfg(test)]
+fn test_dimensions(profile: &Profile) -> Vec<(u32, u32)> {
+    profile.formats.iter().map(|f| (f.w, f.h)).collect()
+}
+
+#[test]
+fn test_profile_inherits_formats() {
+    let config = test_config(r#"
+profiles:
+  - { name: base, color_model: rgb565le, level: 6, formats: [ { w: 10, h: 10 }, { w: 20, h: 5 } ] }
+  - { name: leaf, extends: base, alias: [ l ], formats: [ { w: 3, h: 3 } ] }
+"#);
+    let leaf = config.profile("l").unwrap();
+    assert_eq!(leaf.name, "leaf");
+    assert_eq!(leaf.color_model, "rgb565le");
+    assert_eq!(leaf.level, Some(6));
+    assert!(leaf.extends.is_none());
+    // own formats first.
+    assert_eq!(test_dimensions(&leaf), vec![(3, 3), (10, 10), (20, 5)]);
+}
+
+#[test]
+fn test_profile_removes_formats_at_each_level() {
+    let config = test_config(r#"
+profiles:
+  - { name: base, color_model: rgb565le, formats: [ { w: 10, h: 10 }, { w: 20, h: 5 }, { w: 4, h: 4 } ] }
+  - { name: mid, extends: base, remove_formats: [ { w: 20, h: 5 } ] }
+  - { name: leaf, extends: mid, remove_formats: [ { w: 4, h: 4 } ], formats: [ { w: 4, h: 4 } ] }
+"#);
+    assert_eq!(test_dimensions(&config.profile("mid").unwrap()), vec![(10, 10), (4, 4)]);
+    // leaf's removals don't apply to its own formats.
+    let leaf = config.profile("leaf").unwrap();
+    assert_eq!(test_dimensions(&leaf), vec![(4, 4), (10, 10)]);
+    assert!(leaf.remove_formats.is_none());
+}
+
+#[test]
+fn test_profile_errors() {
+    let config = test_config(r#"
+profiles:
+  - { name: a, extends: b, color_model: rgb565le }
+  - { name: b, extends: a }
+  - { name: orphan, extends: nobody, color_model: rgb565le }
+  - { name: colorless, formats: [ { w: 1, h: 1 } ] }
+"#);
+    assert!(config.profile("a").err().unwrap().to_string().contains("'a' -> 'b' -> 'a'"));
+    assert!(config.profile("orphan").is_err());
+    assert!(config.profile("colorless").err().unwrap().to_string().contains("no color_model"));
+    assert!(config.profile("unknown").is_err());
+}
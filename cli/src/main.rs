@@ -3,14 +3,18 @@ extern crate mtklogo;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
 extern crate serde_yaml;
+extern crate yaml_rust;
 
 use clap::{App, Arg, ArgMatches, SubCommand};
-use command::{emphasize1, err, warn};
+use command::{emphasize1, err, warn, Verbosity};
+use mtklogo::{ColorMode, ContentType};
+use mtklogo::utils::{codec, check_level, Strategy, MAX_LEVEL};
 pub use config::{Config, Format, Profile};
 use std::env;
-use std::error::Error;
-use std::io::{Error as IOError, ErrorKind, Result as IOResult};
+use std::io::{self, Error as IOError, ErrorKind, IsTerminal, Result as IOResult};
 // re-exports entry points.
 use std::path::PathBuf;
 
@@ -20,17 +24,36 @@ mod config;
 // logo generated from http://www.patorjk.com/software/taag/#p=display&h=1&v=3&f=Doom&t=mtklogo
 const LOGO: &'static [u8] = include_bytes!("../resources/logo.txt");
 
+// exit codes, from BSD's sysexits.h.
+/// The command was used incorrectly.
+const EX_USAGE: i32 = 64;
+/// The input data was incorrect.
+const EX_DATAERR: i32 = 65;
+/// An error occurred while doing I/O on some file.
+const EX_IOERR: i32 = 74;
+
 fn main() {
     match wrapped_main() {
         Ok(()) => (),
         Err(e) => {
-            println!("{}: {}", warn("error"), err(e.description()));
-            std::process::exit(1);
+            command::emit(json!({"event": "error", "error": e.to_string()}));
+            command::error(format!("{}: {}", warn("error"), err(&e)));
+            std::process::exit(exit_code(&e));
         }
     }
 }
 
-fn wrapped_main() -> IOResult<()> {
+/// Tells usage errors and data errors from I/O errors.
+fn exit_code(e: &IOError) -> i32 {
+    match e.kind() {
+        ErrorKind::InvalidInput => EX_USAGE,
+        ErrorKind::InvalidData | ErrorKind::Unsupported => EX_DATAERR,
+        _ => EX_IOERR,
+    }
+}
+
+/// Defines the command line.
+fn app() -> App<'static, 'static> {
     // defines common args amongst commands.
     let slots_arg = Arg::with_name("slots")
         .help("Extracts only these slots, other slot remain in raw .z format.")
@@ -39,44 +62,104 @@ fn wrapped_main() -> IOResult<()> {
         .long("slots")
         .conflicts_with("zip");
 
+    let jobs_arg = Arg::with_name("jobs")
+        .help("Number of threads processing slots, 0 (default) uses all cores")
+        .value_name("jobs")
+        .takes_value(true)
+        .short("j")
+        .long("jobs");
+
     let path_arg = Arg::with_name("path")
         .help("Path to input `logo.bin`")
         .required(true)
         .index(1)
         .validator(is_existing_file);
 
-    let prg = App::new("mtklogo")
+    let profile_arg = Arg::with_name("profile")
+        .help("Uses an alternative profile name")
+        .value_name("profile")
+        .short("p")
+        .long("profile");
+
+    let config_arg = Arg::with_name("config")
+        .help("Uses an alternative configuration file")
+        .value_name("configfile")
+        .takes_value(true)
+        .short("c")
+        .long("config")
+        .validator(is_existing_file);
+
+    let mode_arg = Arg::with_name("mode")
+        .help("Overrides profile's color mode")
+        .value_name("mode")
+        .short("m")
+        .long("mode");
+
+    let transform_arg = Arg::with_name("transform")
+        .help("Overrides profile's transform (rotation or mirroring of the device raster)")
+        .value_name("transform")
+        .short("t")
+        .long("transform")
+        .possible_values(&["none", "rot90", "rot180", "rot270", "mirrorh", "mirrorv"]);
+
+    let flip_arg = Arg::with_name("flip")
+        .help("Flips orientation")
+        .short("f")
+        .long("flip");
+
+    // compression backends compiled in this program.
+    let codecs = codec::names();
+    let extensions = ContentType::extensions();
+
+    App::new("mtklogo")
         .version("0.1.2")
         .author("arlept, arnaud@lepoint.net")
         .about("Yet another Android Logo Customizer for MTK devices!\nIt packs or repacks images from an MTK `logo.bin` file.")
+        .arg(Arg::with_name("message-format")
+            .help("Prints results as JSON lines, diagnostics go to stderr")
+            .value_name("format")
+            .takes_value(true)
+            .long("message-format")
+            .default_value("text")
+            .possible_values(&["text", "json"]))
+        .arg(Arg::with_name("verbose")
+            .help("Prints more details")
+            .short("v")
+            .long("verbose"))
+        .arg(Arg::with_name("quiet")
+            .help("Prints errors and results only, no progress messages")
+            .short("q")
+            .long("quiet")
+            .conflicts_with("verbose"))
+        .arg(Arg::with_name("color")
+            .help("Colours the messages: `auto` only on a terminal, unless NO_COLOR is set")
+            .value_name("when")
+            .takes_value(true)
+            .long("color")
+            .default_value("auto")
+            .possible_values(&["auto", "always", "never"]))
+        .arg(Arg::with_name("no-banner")
+            .help("Leaves out the banner")
+            .long("no-banner"))
         .subcommand(SubCommand::with_name("unpack")
             .about("Unpacks a logo image")
-            .arg(Arg::with_name("profile")
-                .help("Uses an alternative profile name")
-                .value_name("profile")
-                .short("p")
-                .long("profile"))
-            .arg(Arg::with_name("config")
-                .help("Uses an alternative configuration file")
-                .value_name("configfile")
-                .takes_value(true)
-                .short("c")
-                .long("config")
-                .validator(is_existing_file))
-            .arg(Arg::with_name("mode")
-                .help("Overrides profile's color mode")
-                .value_name("mode")
-                .short("m")
-                .long("mode"))
-            .arg(Arg::with_name("flip")
-                .help("Flips orientation")
-                .short("f")
-                .long("flip"))
+            .arg(&profile_arg)
+            .arg(&config_arg)
+            .arg(&mode_arg)
+            .arg(&transform_arg)
+            .arg(&flip_arg)
             .arg(Arg::with_name("zip")
                 .help("Do not convert to png, extract as plain .z file")
                 .short("z")
                 .long("zip")
                 .conflicts_with("slots"))
+            .arg(Arg::with_name("format")
+                .help("Exports to another format: images, decoded device bytes (bin) or plain rgba pixels")
+                .value_name("format")
+                .takes_value(true)
+                .long("format")
+                .default_value("png")
+                .possible_values(&extensions))
             .arg(Arg::with_name("output")
                 .help("Sets images output path")
                 .value_name("output")
@@ -91,6 +174,7 @@ fn wrapped_main() -> IOResult<()> {
                 .conflicts_with("output"))
             .arg(&path_arg)
             .arg(&slots_arg)
+            .arg(&jobs_arg)
         )
 
         .subcommand(SubCommand::with_name("explore")
@@ -112,6 +196,94 @@ this is useful is you don't know the image format, you'll probably find out.")
                 .long("width"))
             .arg(&path_arg)
             .arg(&slots_arg)
+            .arg(&jobs_arg)
+        )
+
+        .subcommand(SubCommand::with_name("animate")
+            .about("Previews a sequence of slots (e.g. the charging animation) as an animated GIF")
+            .arg(Arg::with_name("output")
+                .help("Path to output GIF")
+                .value_name("output")
+                .required(true)
+                .takes_value(true)
+                .short("o")
+                .long("output"))
+            .arg(Arg::with_name("slots")
+                .help("Slots to animate, in order (e.g. 3-12 or 3,5,7)")
+                .value_name("slots")
+                .required(true)
+                .takes_value(true)
+                .long("slots"))
+            .arg(Arg::with_name("delay")
+                .help("Delay between frames, in milliseconds")
+                .value_name("delay")
+                .takes_value(true)
+                .default_value("100")
+                .short("d")
+                .long("delay"))
+            .arg(&profile_arg)
+            .arg(&config_arg)
+            .arg(&mode_arg)
+            .arg(&transform_arg)
+            .arg(&flip_arg)
+            .arg(&path_arg)
+        )
+
+        .subcommand(SubCommand::with_name("preview")
+            .about("Draws all slots on a single PNG contact sheet")
+            .arg(Arg::with_name("output")
+                .help("Path to output PNG")
+                .value_name("output")
+                .required(true)
+                .takes_value(true)
+                .short("o")
+                .long("output"))
+            .arg(Arg::with_name("columns")
+                .help("Number of slots per row")
+                .value_name("columns")
+                .takes_value(true)
+                .default_value("8")
+                .long("columns"))
+            .arg(Arg::with_name("cell")
+                .help("Size of a slot thumbnail, in pixels")
+                .value_name("cell")
+                .takes_value(true)
+                .default_value("128")
+                .long("cell"))
+            .arg(&profile_arg)
+            .arg(&config_arg)
+            .arg(&mode_arg)
+            .arg(&transform_arg)
+            .arg(&flip_arg)
+            .arg(&path_arg)
+        )
+
+        .subcommand(SubCommand::with_name("show")
+            .about("Renders a slot in the terminal (requires truecolor support)")
+            .arg(Arg::with_name("slot")
+                .help("Slot to show")
+                .value_name("slot")
+                .required(true)
+                .takes_value(true)
+                .short("s")
+                .long("slot"))
+            .arg(Arg::with_name("width")
+                .help("Image width in pixels, when the profile cannot tell (like explore)")
+                .value_name("width")
+                .takes_value(true)
+                .short("w")
+                .long("width"))
+            .arg(Arg::with_name("columns")
+                .help("Terminal width in characters (defaults to the terminal's, or $COLUMNS)")
+                .value_name("columns")
+                .takes_value(true)
+                .long("columns"))
+            .arg(&profile_arg)
+            .arg(&config_arg)
+            .arg(&mode_arg)
+            .arg(&transform_arg)
+            .arg(&flip_arg)
+            .arg(&path_arg)
         )
 
         .subcommand(SubCommand::with_name("guess")
@@ -126,6 +298,124 @@ Note: the program may be very slow if your input size is a large prime number!")
                 .long("size"))
         )
 
+        .subcommand(SubCommand::with_name("config")
+            .about("Inspects the configuration files")
+            .subcommand(SubCommand::with_name("check")
+                .about("Checks the configuration files, reporting every problem with its line")
+                .arg(&config_arg))
+            .subcommand(SubCommand::with_name("path")
+                .about("Lists the configuration files, from the highest priority")
+                .arg(&config_arg))
+            .subcommand(SubCommand::with_name("list")
+                .about("Lists the profiles: name, color mode, formats count and aliases")
+                .arg(&config_arg))
+            .subcommand(SubCommand::with_name("init")
+                .about("Writes the built-in profiles to the user's configuration file, to be edited")
+                .arg(Arg::with_name("output")
+                    .help("Configuration file to write, instead of the user's one")
+                    .value_name("file")
+                    .takes_value(true)
+                    .short("o")
+                    .long("output"))
+                .arg(Arg::with_name("force")
+                    .help("Overwrites an existing file")
+                    .long("force")))
+            .subcommand(SubCommand::with_name("show")
+                .about("Prints a profile, as resolved by the other commands")
+                .arg(Arg::with_name("profile")
+                    .help("Profile name or alias")
+                    .required(true)
+                    .index(1))
+                .arg(&config_arg)
+                .arg(&mode_arg)
+                .arg(&transform_arg)
+                .arg(Arg::with_name("format")
+                    .help("Output format")
+                    .value_name("format")
+                    .takes_value(true)
+                    .long("format")
+                    .default_value("yaml")
+                    .possible_values(&["yaml", "json"])))
+        )
+
+        .subcommand(SubCommand::with_name("profile")
+            .about("Manages device profiles")
+            .subcommand(SubCommand::with_name("learn")
+                .about("Writes a new profile, with the dimensions of every slot of a logo image")
+                .arg(Arg::with_name("path")
+                    .help("Path to input `logo.bin`")
+                    .required_unless("from-dir")
+                    .index(1)
+                    .validator(is_existing_file))
+                .arg(Arg::with_name("width")
+                    .help("Width of the full screen images, in pixels")
+                    .value_name("width")
+                    .required_unless("from-dir")
+                    .takes_value(true)
+                    .short("w")
+                    .long("width"))
+                .arg(Arg::with_name("mode")
+                    .help("Color mode of the device")
+                    .value_name("mode")
+                    .required_unless("from-dir")
+                    .takes_value(true)
+                    .short("m")
+                    .long("mode"))
+                .arg(Arg::with_name("name")
+                    .help("Name of the new profile")
+                    .value_name("name")
+                    .required(true)
+                    .takes_value(true)
+                    .short("n")
+                    .long("name"))
+                .arg(Arg::with_name("from-dir")
+                    .help("Learns from the images left in an explore directory, instead of a logo image")
+                    .value_name("directory")
+                    .takes_value(true)
+                    .long("from-dir")
+                    .conflicts_with_all(&["path", "width", "mode"])
+                    .validator(is_existing_directory))
+                .arg(Arg::with_name("output")
+                    .help("Configuration file to write, instead of a fragment of the user's configuration")
+                    .value_name("output")
+                    .takes_value(true)
+                    .short("o")
+                    .long("output"))
+                .arg(&config_arg))
+            .subcommand(SubCommand::with_name("import")
+                .about("Writes a new profile, with the dimensions listed by another MTK logo tool")
+                .arg(Arg::with_name("tool")
+                    .help("Tool whose table is imported")
+                    .required(true)
+                    .possible_values(command::IMPORT_TOOLS)
+                    .index(1))
+                .arg(Arg::with_name("path")
+                    .help("Path to the tool's source file listing the dimensions")
+                    .required(true)
+                    .index(2)
+                    .validator(is_existing_file))
+                .arg(Arg::with_name("mode")
+                    .help("Color mode of the device")
+                    .value_name("mode")
+                    .required(true)
+                    .takes_value(true)
+                    .short("m")
+                    .long("mode"))
+                .arg(Arg::with_name("name")
+                    .help("Name of the new profile")
+                    .value_name("name")
+                    .required(true)
+                    .takes_value(true)
+                    .short("n")
+                    .long("name"))
+                .arg(Arg::with_name("output")
+                    .help("Configuration file to write, instead of a fragment of the user's configuration")
+                    .value_name("output")
+                    .takes_value(true)
+                    .short("o")
+                    .long("output")))
+        )
+
         .subcommand(SubCommand::with_name("repack")
             .about("Repacks a logo image")
             .arg(Arg::with_name("output")
@@ -144,30 +434,101 @@ Note: the program may be very slow if your input size is a large prime number!")
                 .help("Strips Alpha channel, assume image is opaque")
                 .short("a")
                 .long("alpha"))
+            .arg(Arg::with_name("level")
+                .help("Compression level, from 0 (none) to 9 (best), overrides profile's")
+                .value_name("level")
+                .takes_value(true)
+                .short("l")
+                .long("level"))
+            .arg(Arg::with_name("strategy")
+                .help("Compression strategy, overrides profile's")
+                .value_name("strategy")
+                .takes_value(true)
+                .long("strategy")
+                .possible_values(&["default", "filtered", "huffman", "rle", "fixed"]))
+            .arg(Arg::with_name("codec")
+                .help("Compression backend, to compare them")
+                .value_name("codec")
+                .takes_value(true)
+                .long("codec")
+                .possible_values(&codecs))
+            .arg(Arg::with_name("max-compression")
+                .help("Exhaustive compression (zopfli), much slower but smaller than level 9")
+                .long("max-compression")
+                .conflicts_with_all(&["level", "strategy", "codec"]))
+            .arg(Arg::with_name("match")
+                .help("Original `logo.bin`: tries every compression level to reproduce its slots byte for byte")
+                .value_name("original")
+                .takes_value(true)
+                .long("match")
+                .validator(is_existing_file))
+            .arg(&profile_arg)
+            .arg(&config_arg)
+            .arg(&jobs_arg)
         )
-    ;
-    let matches = prg.get_matches();
+}
+
+fn wrapped_main() -> IOResult<()> {
+    let matches = match app().get_matches_safe() {
+        Ok(matches) => matches,
+        Err(e) => match e.kind {
+            clap::ErrorKind::HelpDisplayed | clap::ErrorKind::VersionDisplayed => e.exit(),
+            _ => {
+                command::error(e.message);
+                std::process::exit(EX_USAGE);
+            }
+        }
+    };
 
-    println!("{}", emphasize1(String::from_utf8_lossy(LOGO)));
+    command::set_json(matches.value_of("message-format") == Some("json"));
+    command::set_verbosity(if matches.is_present("quiet") {
+        Verbosity::Quiet
+    } else if matches.is_present("verbose") {
+        Verbosity::Verbose
+    } else {
+        Verbosity::Normal
+    });
+    // a profile shown is a document, which the prose must not get into.
+    command::set_document(matches.subcommand_matches("config")
+        .is_some_and(|matches| matches.subcommand_matches("show").is_some()));
+    command::set_colored(match matches.value_of("color") {
+        Some("always") => true,
+        Some("never") => false,
+        // prose goes to stderr along with JSON results, it's left plain.
+        _ => env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            && !command::is_json() && io::stdout().is_terminal(),
+    });
+    if !command::is_json() && !matches.is_present("no-banner") {
+        command::say(emphasize1(String::from_utf8_lossy(LOGO)));
+    }
 
     if let Some(matches) = matches.subcommand_matches("unpack") {
-        let config = solve_config(matches)?;
-        let profile = matches.value_of("profile").unwrap_or("default");
-        let mode = matches.value_of("mode");
-        let flip = matches.is_present("flip");
-        let zip = matches.is_present("zip");
-        let check = matches.is_present("no-out");
         let path = solve_path(matches)?;
+        // without --profile, the logo image tells which one it is, its measured slots are not measured again.
+        let (profile, traits) = if matches.is_present("profile") {
+            (solve_profile(matches)?, None)
+        } else {
+            let (profile, traits) = command::select_profile(&solve_config(matches)?, &path)?;
+            (with_overrides(matches, profile), Some(traits))
+        };
         let output = solve_output(matches)?;
-        let slots = solve_slots(matches)?;
+        let options = command::UnpackOptions {
+            slots: solve_slots(matches)?,
+            flip: matches.is_present("flip"),
+            zip: matches.is_present("zip"),
+            check: matches.is_present("no-out"),
+            format: matches.value_of("format").unwrap_or("png").to_string(),
+            jobs: solve_jobs(matches)?,
+        };
 
-        command::run_unpack(config, slots, profile, mode, flip, zip, check, path, output)
+        command::run_unpack(profile, traits, path, output, options)
     } else if let Some(matches) = matches.subcommand_matches("explore") {
         let path = solve_path(matches)?;
         let output = solve_output(matches)?;
         let width = parse_or_error::<u32>(matches, "width")?;
         let slots = solve_slots(matches)?;
-        command::run_explore(path, slots, output, width)
+        let jobs = solve_jobs(matches)?;
+        command::run_explore(path, slots, output, width, jobs)
     } else if let Some(matches) = matches.subcommand_matches("repack") {
         let maybe_files = matches.values_of("files")
             .map(|vals| vals.collect::<Vec<_>>());
@@ -182,12 +543,102 @@ Note: the program may be very slow if your input size is a large prime number!")
             .map(|o| PathBuf::from(o))
             .unwrap_or(PathBuf::default());
         let strip_alpha = matches.is_present("alpha");
-        command::run_repack(output, paths, strip_alpha)
+        // profile is optional, it gives default compression settings and the slots to check.
+        let profile = if matches.is_present("profile") || matches.is_present("config") {
+            Some(solve_profile(matches)?)
+        } else {
+            None
+        };
+        let (mut level, mut strategy) = match profile {
+            Some(ref profile) => profile.compression()?,
+            None => (MAX_LEVEL, Strategy::Default),
+        };
+        if let Some(l) = parse_if_present::<u32>(matches, "level")? {
+            level = check_level(l)?;
+        }
+        if let Some(s) = matches.value_of("strategy") {
+            strategy = Strategy::by_name(s)?;
+        }
+        // maximum compression is zopfli's business.
+        let codec = match matches.value_of("codec") {
+            Some(name) => codec::by_name(name, level, strategy)?,
+            None if matches.is_present("max-compression") => codec::by_name("zopfli", level, strategy)?,
+            None => codec::default_codec(level, strategy)?,
+        };
+        let reference = matches.value_of("match").map(PathBuf::from);
+        let jobs = solve_jobs(matches)?;
+        command::run_repack(output, paths, strip_alpha, codec, reference, profile, jobs)
+    } else if let Some(matches) = matches.subcommand_matches("animate") {
+        let profile = solve_profile(matches)?;
+        let flip = matches.is_present("flip");
+        let path = solve_path(matches)?;
+        let output = solve_path_of(matches, "output")?;
+        let slots = solve_slots(matches)?.unwrap_or_default();
+        let delay = parse_or_error::<u32>(matches, "delay")?;
+        command::run_animate(profile, slots, delay, flip, path, output)
+    } else if let Some(matches) = matches.subcommand_matches("preview") {
+        let profile = solve_profile(matches)?;
+        let flip = matches.is_present("flip");
+        let path = solve_path(matches)?;
+        let output = solve_path_of(matches, "output")?;
+        let columns = parse_or_error::<u32>(matches, "columns")?;
+        let cell = parse_or_error::<u32>(matches, "cell")?;
+        command::run_preview(profile, flip, columns, cell, path, output)
+    } else if let Some(matches) = matches.subcommand_matches("show") {
+        let profile = solve_profile(matches)?;
+        let flip = matches.is_present("flip");
+        let path = solve_path(matches)?;
+        let slot = parse_or_error::<usize>(matches, "slot")?;
+        let width = parse_if_present::<u32>(matches, "width")?;
+        let columns = parse_if_present::<u32>(matches, "columns")?;
+        command::run_show(profile, slot, width, columns, flip, path)
     } else if let Some(matches) = matches.subcommand_matches("guess") {
         let size = parse_or_error::<usize>(matches, "size")?;
         command::run_guess(size)
+    } else if let Some(matches) = matches.subcommand_matches("profile") {
+        if let Some(matches) = matches.subcommand_matches("learn") {
+            let name = value_or_error(matches, "name")?;
+            let output = matches.value_of("output").map(PathBuf::from);
+            match matches.value_of("from-dir") {
+                Some(dir) => command::run_learn_from_dir(PathBuf::from(dir), name, output),
+                None => {
+                    let path = solve_path(matches)?;
+                    let width = parse_or_error::<u32>(matches, "width")?;
+                    let mode = ColorMode::by_name(&value_or_error(matches, "mode")?)?;
+                    // known profiles may tell the dimensions of the smaller images.
+                    let known = solve_config(matches).ok();
+                    command::run_learn(path, width, mode, name, known, output)
+                }
+            }
+        } else if let Some(matches) = matches.subcommand_matches("import") {
+            let tool = value_or_error(matches, "tool")?;
+            let path = solve_path(matches)?;
+            let mode = ColorMode::by_name(&value_or_error(matches, "mode")?)?;
+            let name = value_or_error(matches, "name")?;
+            let output = matches.value_of("output").map(PathBuf::from);
+            command::run_import(&tool, path, mode, name, output)
+        } else {
+            command::say(matches.usage());
+            Err(IOError::new(ErrorKind::InvalidInput, "unrecognized profile command."))
+        }
+    } else if let Some(matches) = matches.subcommand_matches("config") {
+        if let Some(matches) = matches.subcommand_matches("check") {
+            command::run_config_check(solve_config_paths(matches)?, !matches.is_present("config"))
+        } else if let Some(matches) = matches.subcommand_matches("path") {
+            command::run_config_path(solve_config_paths(matches)?, !matches.is_present("config"))
+        } else if let Some(matches) = matches.subcommand_matches("list") {
+            command::run_config_list(solve_config(matches)?)
+        } else if let Some(matches) = matches.subcommand_matches("init") {
+            command::run_config_init(matches.value_of("output").map(PathBuf::from), matches.is_present("force"))
+        } else if let Some(matches) = matches.subcommand_matches("show") {
+            let json = matches.value_of("format") == Some("json");
+            command::run_config_show(solve_profile(matches)?, json)
+        } else {
+            command::say(matches.usage());
+            Err(IOError::new(ErrorKind::InvalidInput, "unrecognized config command."))
+        }
     } else {
-        println!("{}", matches.usage());
+        command::say(matches.usage());
         Err(IOError::new(ErrorKind::InvalidInput, "unrecognized command arguments."))
     }
 }
@@ -205,6 +656,19 @@ fn parse_or_error<T>(matches: &ArgMatches, label: &str) -> IOResult<T>
             ErrorKind::InvalidInput, format!("'{}' has not expected format", label))))
 }
 
+fn parse_if_present<T>(matches: &ArgMatches, label: &str) -> IOResult<Option<T>>
+    where T: std::str::FromStr {
+    if matches.is_present(label) {
+        parse_or_error::<T>(matches, label).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// 0 lets the thread pool use all cores.
+fn solve_jobs(matches: &ArgMatches) -> IOResult<usize> {
+    parse_if_present::<usize>(matches, "jobs").map(|jobs| jobs.unwrap_or(0))
+}
 
 fn solve_output(matches: &ArgMatches) -> IOResult<PathBuf> {
     value_or_error(matches, "output")
@@ -213,6 +677,9 @@ fn solve_output(matches: &ArgMatches) -> IOResult<PathBuf> {
 }
 
 fn solve_config(matches: &ArgMatches) -> IOResult<Config> {
+    for path in solve_config_paths(matches)? {
+        command::detail(format!("reading configuration {}.", emphasize1(path.display())));
+    }
     match matches.value_of("config") {
         Some(c) => {
             Config::from_file(PathBuf::from(c).as_path())
@@ -221,22 +688,71 @@ fn solve_config(matches: &ArgMatches) -> IOResult<Config> {
     }
 }
 
+/// The configuration files: the user's one, or all those which are found.
+fn solve_config_paths(matches: &ArgMatches) -> IOResult<Vec<PathBuf>> {
+    match matches.value_of("config") {
+        Some(c) => Ok(vec![PathBuf::from(c)]),
+        None => Config::config_paths()
+    }
+}
+
+/// Resolves the active profile, along with user's overrides.
+fn solve_profile(matches: &ArgMatches) -> IOResult<Profile> {
+    let config = solve_config(matches)?;
+    Ok(with_overrides(matches, config.profile(matches.value_of("profile").unwrap_or("default"))?))
+}
+
+/// Applies user's overrides to a profile.
+fn with_overrides(matches: &ArgMatches, profile: Profile) -> Profile {
+    let mut profile = profile;
+    // User may override color model.
+    if let Some(model) = matches.value_of("mode") {
+        profile = profile.with_color_model(String::from(model));
+    };
+    // User may override default transform.
+    if let Some(transform) = matches.value_of("transform") {
+        profile = profile.with_transform(String::from(transform));
+    };
+    profile
+}
+
 fn solve_path(matches: &ArgMatches) -> IOResult<PathBuf> {
-    value_or_error(matches, "path").map(|p| PathBuf::from(p))
+    solve_path_of(matches, "path")
+}
+
+fn solve_path_of(matches: &ArgMatches, label: &str) -> IOResult<PathBuf> {
+    value_or_error(matches, label).map(PathBuf::from)
 }
 
-fn solve_slots(matches: &ArgMatches) -> IOResult<Option<Vec<usize>>> {
+fn solve_slots(matches: &ArgMatches) -> IOResult<Option<command::Slots>> {
+    fn parse_slot(s: &str) -> IOResult<usize> {
+        s.parse::<usize>()
+            .map_err(|_| IOError::new(
+                ErrorKind::InvalidInput, format!("'{}' is not an integer", s)))
+    }
     match matches.value_of("slots") {
         Some(slots) => {
             let tokens: Vec<&str> = slots.split(',').collect();
-            let mut sizes: Vec<usize> = Vec::with_capacity(tokens.len());
+            let mut ranges = Vec::with_capacity(tokens.len());
             for s in tokens.iter() {
-                let value = s.parse::<usize>()
-                    .map_err(|_| IOError::new(
-                        ErrorKind::InvalidInput, format!("'{}' is not an integer", s)))?;
-                sizes.push(value);
+                // a token is either a single slot, or an inclusive range "first-last".
+                match s.find('-') {
+                    Some(dash) => {
+                        let first = parse_slot(&s[..dash])?;
+                        let last = parse_slot(&s[dash + 1..])?;
+                        if last < first {
+                            return Err(IOError::new(
+                                ErrorKind::InvalidInput, format!("'{}' is not an ascending range", s)));
+                        }
+                        ranges.push(first..=last);
+                    }
+                    None => {
+                        let slot = parse_slot(s)?;
+                        ranges.push(slot..=slot);
+                    }
+                }
             }
-            Ok(Some(sizes))
+            Ok(Some(command::Slots(ranges)))
         }
         None => Ok(None)
     }
@@ -258,3 +774,20 @@ fn is_existing_file(val: String) -> Result<(), String> {
         Err(String::from("must be an existing file."))
     }
 }
+
+#[test]
+fn test_message_format_leaves_output_to_commands() {
+    let dir = env::temp_dir();
+    let file = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+    let matches = app().get_matches_from_safe(vec![
+        "mtklogo", "--message-format", "json", "unpack", "--output", dir.to_str().unwrap(), file,
+    ]).unwrap();
+    assert_eq!(matches.value_of("message-format"), Some("json"));
+    let unpack = matches.subcommand_matches("unpack").unwrap();
+    assert_eq!(unpack.value_of("output"), dir.to_str());
+    // a command's own --output doesn't switch to JSON.
+    let matches = app().get_matches_from_safe(vec![
+        "mtklogo", "unpack", "--output", dir.to_str().unwrap(), file,
+    ]).unwrap();
+    assert_eq!(matches.value_of("message-format"), Some("text"));
+}
@@ -1,6 +1,9 @@
 extern crate byteorder;
 
 pub use mtk::{LogoImage, LogoTable, MtkHeader, MtkType};
+use utils::encoding::Encoding;
+use utils::formats::{self, ImageFormat};
+use utils::image::Transform;
 use std::fmt;
 use std::fmt::Display;
 use std::io::{Error as IOError, ErrorKind, Result};
@@ -25,6 +28,16 @@ pub enum ColorMode {
     Rgb565(Endian),
 }
 
+/// How each mode lays out a pixel in device memory, as 'pivot' RGBA channels go:
+/// - rgbabe: |r|g|b|a|, the 32 bits word 0xRRGGBBAA, big endian.
+/// - rgbale: |a|b|g|r|, the same word, little endian.
+/// - bgrabe: |b|g|r|a|, the 32 bits word 0xBBGGRRAA, big endian.
+/// - bgrale: |a|r|g|b|, the same word, little endian.
+/// - rgb565be: |rrrrrggg|gggbbbbb|, the 16 bits word RRRRRGGGGGGBBBBB, big endian.
+/// - rgb565le: |gggbbbbb|rrrrrggg|, the same word, little endian.
+///
+/// rgb565 keeps the high bits of each channel and drops alpha: decoding gives the low bits back
+/// as zeros, and full opacity.
 static SUPPORTED_MODES: [(ColorMode, &str); 6] = [
     (ColorMode::Rgba(Endian::Big), "rgbabe"),
     (ColorMode::Rgba(Endian::Little), "rgbale"),
@@ -77,18 +90,42 @@ impl ColorMode {
 pub enum ContentType {
     /// Plain zlib encoded data,
     Z,
-    /// A PNG image which is meant for the specified color mode of the device.
-    PNG(ColorMode),
+    /// An image file which is meant for the specified color mode of the device.
+    Image(ColorMode, ImageFormat),
+    /// Decoded device bytes, just as the device reads them.
+    Bin(ColorMode),
+    /// Uncompressed RGBA pixels, with no header: they keep the device order, whatever the transform.
+    Rgba(ColorMode),
 }
 
 impl ContentType {
     /// Given a file name, can we say which Content Type it is?
     pub fn from_name(name: &str) -> Option<Self> {
-        match SUPPORTED_MODES.iter().find(|(_, mode_name)| {
-            name.ends_with(&format!("{}.png", mode_name))
-        }).map(|(mode, _)| mode.clone()) {
-            Some(mode) => Some(ContentType::PNG(mode)),
-            None => if name.ends_with("raw.z") { Some(ContentType::Z) } else { None }
+        let (stem, extension) = match name.rfind('.') {
+            Some(dot) => (&name[..dot], &name[dot + 1..]),
+            None => return None,
+        };
+        if extension == "z" {
+            return if stem.ends_with("raw") { Some(ContentType::Z) } else { None };
+        }
+        SUPPORTED_MODES.iter()
+            .find(|(_, mode_name)| stem.ends_with(mode_name))
+            .and_then(|(mode, _)| ContentType::by_extension(extension, mode).ok())
+    }
+
+    /// Names of the formats slots may be exported to, which are also file extensions.
+    pub fn extensions() -> Vec<&'static str> {
+        let mut extensions = formats::names();
+        extensions.extend_from_slice(&["bin", "rgba"]);
+        extensions
+    }
+
+    /// Gets the Content Type of an extension, for the specified color mode of the device.
+    pub fn by_extension(extension: &str, color_mode: &ColorMode) -> Result<Self> {
+        match extension {
+            "bin" => Ok(ContentType::Bin(color_mode.clone())),
+            "rgba" => Ok(ContentType::Rgba(color_mode.clone())),
+            _ => ImageFormat::by_name(extension).map(|format| ContentType::Image(color_mode.clone(), format)),
         }
     }
 }
@@ -97,22 +134,74 @@ impl ContentType {
 /// A structure to gather information about an image/slot.
 pub struct FileInfo {
     pub id: usize,
+    /// Name of the slot, as the profile tells it (e.g. "boot").
+    pub name: Option<String>,
     pub content_type: ContentType,
+    /// How the device raster was turned into the exported image.
+    pub transform: Transform,
+    /// How the slot is stored in the logo image.
+    pub encoding: Encoding,
 }
 
 impl FileInfo {
     /// How would we name the file for this image/slot?
+    /// Tags for the default transform and encoding are left out.
     pub fn filename(&self) -> String {
-        match self.content_type {
-            ContentType::Z => format!("logo_{:03}_raw.z", self.id),
-            ContentType::PNG(ref mode) => format!("logo_{:03}_{}.png", self.id, mode),
+        let mut tags = String::new();
+        if let Some(ref name) = self.name {
+            tags.push_str(&format!("_{}", name));
+        }
+        let (mode, extension) = match self.content_type {
+            ContentType::Z => return format!("logo_{:03}{}_raw.z", self.id, tags),
+            ContentType::Image(ref mode, format) => (mode, format.to_string()),
+            ContentType::Bin(ref mode) => (mode, "bin".to_string()),
+            ContentType::Rgba(ref mode) => (mode, "rgba".to_string()),
+        };
+        if self.transform != Transform::Identity {
+            tags.push_str(&format!("_{}", self.transform));
         }
+        if self.encoding != Encoding::Zlib {
+            tags.push_str(&format!("_{}", self.encoding));
+        }
+        format!("logo_{:03}{}_{}.{}", self.id, tags, mode, extension)
     }
 
     pub fn from_info(id: usize, zip: bool, color_model: &ColorMode) -> Self {
         FileInfo {
             id,
-            content_type: if zip { ContentType::Z } else { ContentType::PNG(color_model.clone()) },
+            name: None,
+            content_type: if zip { ContentType::Z } else { ContentType::Image(color_model.clone(), ImageFormat::Png) },
+            transform: Transform::Identity,
+            encoding: Encoding::Zlib,
+        }
+    }
+
+    pub fn with_name(self, name: Option<String>) -> Self {
+        FileInfo { name, ..self }
+    }
+
+    pub fn with_content_type(self, content_type: ContentType) -> Self {
+        FileInfo { content_type, ..self }
+    }
+
+    pub fn with_transform(self, transform: Transform) -> Self {
+        FileInfo { transform, ..self }
+    }
+
+    pub fn with_encoding(self, encoding: Encoding) -> Self {
+        FileInfo { encoding, ..self }
+    }
+
+    /// Tells whether a slot name reads back from a file name: a token between '_' must not be a transform
+    /// nor an encoding tag, and a file name does not go to another directory.
+    pub fn check_name(name: &str) -> Result<()> {
+        if name.is_empty() || name.contains(['/', '\\']) {
+            return Err(IOError::new(ErrorKind::InvalidInput, format!("slot name '{}' is not a file name", name)));
+        }
+        match name.split('_').find(|token| Transform::by_name(token).is_ok() || Encoding::by_name(token).is_ok()) {
+            Some(tag) => Err(IOError::new(ErrorKind::InvalidInput,
+                                          format!("slot name '{}' holds '{}', a tag of file names", name, tag))),
+            None => Ok(()),
         }
     }
 
@@ -124,12 +213,73 @@ impl FileInfo {
             || Err(IOError::new(ErrorKind::InvalidInput, "cannot find '_id_' token")),
             |middle_id| Ok(middle_id)).and_then(|middle_id| middle_id.parse::<usize>().map_err(
             |_| IOError::new(ErrorKind::InvalidInput, "cannot parse '_id' token")))?;
+        // Optional tags lie between the id and the trailing encoding: "xxx_id_tag_yyy".
+        let tags = if tokens.len() > 3 { &tokens[2..tokens.len() - 1] } else { &[] as &[&str] };
+        let transform = tags.iter()
+            .filter_map(|tag| Transform::by_name(tag).ok())
+            .next_back()
+            .unwrap_or(Transform::Identity);
+        let encoding = tags.iter()
+            .filter_map(|tag| Encoding::by_name(tag).ok())
+            .next_back()
+            .unwrap_or(Encoding::Zlib);
+        // other tags make the name of the slot.
+        let names: Vec<&str> = tags.iter()
+            .filter(|tag| Transform::by_name(tag).is_err() && Encoding::by_name(tag).is_err())
+            .cloned()
+            .collect();
+        let slot_name = if names.is_empty() { None } else { Some(names.join("_")) };
         if let Some(content_type) = ContentType::from_name(name) {
-            Ok(FileInfo { id, content_type })
+            Ok(FileInfo { id, name: slot_name, content_type, transform, encoding })
         } else {
             Err(IOError::new(ErrorKind::InvalidInput,
                              format!(
-                                 "file '{}' does not look like a .z or a supported png format", name)))
+                                 "file '{}' does not look like a .z or a supported image format", name)))
         }
     }
 }
+
+#[test]
+fn test_file_names_carry_tags() {
+    let mode = ColorMode::by_name("rgb565le").unwrap();
+    let plain = FileInfo::from_info(7, false, mode);
+    assert_eq!(plain.filename(), "logo_007_rgb565le.png");
+    let tagged = plain.with_transform(Transform::Rotate90).with_encoding(Encoding::Gzip);
+    assert_eq!(tagged.filename(), "logo_007_rot90_gzip_rgb565le.png");
+    let parsed = FileInfo::from_name("/tmp/logos/logo_007_rot90_gzip_rgb565le.png").unwrap();
+    assert_eq!((parsed.id, parsed.transform, parsed.encoding), (7, Transform::Rotate90, Encoding::Gzip));
+    let parsed = FileInfo::from_name("logo_012_raw_bgrabe.png").unwrap();
+    assert_eq!((parsed.id, parsed.transform, parsed.encoding), (12, Transform::Identity, Encoding::Raw));
+    assert_eq!(FileInfo::from_name("logo_003_raw.z").unwrap().encoding, Encoding::Zlib);
+    match FileInfo::from_name("logo_004_rot180_rgbale.tga").unwrap() {
+        FileInfo { id: 4, content_type: ContentType::Image(ColorMode::Rgba(Endian::Little), ImageFormat::Tga),
+            transform: Transform::Rotate180, .. } => (),
+        other => panic!("{:?}", other),
+    }
+    assert!(FileInfo::from_name("logo_005_rgbale.jpg").is_err());
+    let named = FileInfo::from_info(40, false, mode).with_name(Some("charging_3".to_string()));
+    assert_eq!(named.clone().with_encoding(Encoding::Raw).filename(), "logo_040_charging_3_raw_rgb565le.png");
+    let parsed = FileInfo::from_name("logo_040_charging_3_raw_rgb565le.png").unwrap();
+    assert_eq!((parsed.id, parsed.name, parsed.encoding), (40, Some("charging_3".to_string()), Encoding::Raw));
+    let zipped = FileInfo::from_info(1, true, mode).with_name(Some("boot".to_string()));
+    assert_eq!(zipped.filename(), "logo_001_boot_raw.z");
+    assert_eq!(FileInfo::from_name(&zipped.filename()).unwrap().name, Some("boot".to_string()));
+    let dump = FileInfo::from_info(6, false, mode).with_content_type(ContentType::Bin(mode.clone()));
+    assert_eq!(dump.with_encoding(Encoding::Lz4).filename(), "logo_006_lz4_rgb565le.bin");
+    match FileInfo::from_name("logo_006_lz4_rgb565le.bin").unwrap() {
+        FileInfo { content_type: ContentType::Bin(ColorMode::Rgb565(Endian::Little)), encoding: Encoding::Lz4, .. } => (),
+        other => panic!("{:?}", other),
+    }
+    for extension in ContentType::extensions() {
+        let info = FileInfo::from_info(8, false, mode)
+            .with_content_type(ContentType::by_extension(extension, mode).unwrap());
+        assert_eq!(FileInfo::from_name(&info.filename()).unwrap().filename(), info.filename());
+    }
+    // names would be read back as tags, or leave the output directory.
+    for name in ["raw", "lz4", "gzip", "zlib", "rot90", "mirrorh", "none", "boot_raw", "../boot", "a\\b", ""].iter() {
+        assert!(FileInfo::check_name(name).is_err(), "{}", name);
+    }
+    for name in ["boot", "charging_3", "low_battery"].iter() {
+        assert!(FileInfo::check_name(name).is_ok(), "{}", name);
+    }
+}
@@ -1,6 +1,8 @@
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::io::{Error as IOError, ErrorKind, Read, Result, Seek, SeekFrom, Write};
 use super::header::{MtkHeader, MtkType};
+use utils::codec::Codec;
+use utils::encoding::Encoding;
 
 /// The raw logo binary's header, we only keep "relevant" information.
 /// Data like padding or non-meaningful bytes are not preserved.
@@ -116,6 +118,19 @@ impl LogoImage {
         LogoImage { table, blobs }
     }
 
+    /// Given a list of device rasters, deflates them with the codec and creates a complete logo image.
+    pub fn encode(rasters: &[Vec<u8>], codec: &dyn Codec) -> Result<LogoImage> {
+        let blobs = rasters.iter()
+            .map(|raster| codec.deflate(raster))
+            .collect::<Result<Vec<Vec<u8>>>>()?;
+        Ok(LogoImage::new_blobs(blobs))
+    }
+
+    /// Decodes all blobs with the codec, whatever their encoding, giving back the device rasters.
+    pub fn decode(&self, codec: &dyn Codec) -> Result<Vec<Vec<u8>>> {
+        self.blobs.iter().map(|blob| Encoding::detect(blob).decode(blob, codec)).collect()
+    }
+
     /// Writes this complete logo image to the specified writer.
     pub fn write<W: Write>(&self, mut writer: &mut W) -> Result<()> {
         self.table.write(&mut writer)?;
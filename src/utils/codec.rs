@@ -0,0 +1,106 @@
+use std::io::{Error as IOError, ErrorKind, Read, Result, Write};
+use super::Strategy;
+use super::encoding::zlib_to_gzip;
+
+/// A compression backend, chosen at runtime.
+pub trait Codec: Send + Sync {
+    /// Backend name, as known by `by_name`.
+    fn name(&self) -> &'static str;
+    /// Compression level used when deflating.
+    fn level(&self) -> u32;
+    /// Whether the level and strategy change what is deflated, see `find_level`.
+    fn has_settings(&self) -> bool { true }
+    /// Inflates a zlib stream.
+    fn inflate(&self, data: &[u8]) -> Result<Vec<u8>>;
+    /// Deflates data as a zlib stream.
+    fn deflate(&self, data: &[u8]) -> Result<Vec<u8>>;
+    /// Inflates a gzip stream.
+    fn gunzip(&self, data: &[u8]) -> Result<Vec<u8>>;
+    /// Deflates data as a gzip stream, which holds the same compressed data as zlib.
+    fn gzip(&self, data: &[u8]) -> Result<Vec<u8>> {
+        zlib_to_gzip(&self.deflate(data)?, data)
+    }
+    /// Inflates a zlib stream as it's read.
+    fn inflate_stream<'a>(&self, data: &'a [u8]) -> Result<Box<dyn Read + 'a>>;
+    /// Deflates data as it's written. Backends which cannot stream buffer the data until finished.
+    fn deflate_stream<'a>(&'a self) -> Result<Box<dyn Sink + 'a>> {
+        Ok(Box::new(Buffered::new(move |data: &[u8]| self.deflate(data))))
+    }
+}
+
+/// Where data is written to be encoded, the whole encoded stream is given back when finished.
+pub trait Sink: Write {
+    fn finish(self: Box<Self>) -> Result<Vec<u8>>;
+}
+
+/// Data left as it is.
+impl Sink for Vec<u8> {
+    fn finish(self: Box<Self>) -> Result<Vec<u8>> {
+        Ok(*self)
+    }
+}
+
+/// Keeps all data in memory, and encodes it at once when finished.
+pub struct Buffered<F> {
+    data: Vec<u8>,
+    encode: F,
+}
+
+impl<F: Fn(&[u8]) -> Result<Vec<u8>>> Buffered<F> {
+    pub fn new(encode: F) -> Self {
+        Buffered { data: Vec::new(), encode }
+    }
+}
+
+impl<F> Write for Buffered<F> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.data.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<F: Fn(&[u8]) -> Result<Vec<u8>>> Sink for Buffered<F> {
+    fn finish(self: Box<Self>) -> Result<Vec<u8>> {
+        (self.encode)(&self.data)
+    }
+}
+
+/// Lists the backends compiled in this program.
+pub fn names() -> Vec<&'static str> {
+    let mut names = Vec::new();
+    if cfg!(feature = "flate2") { names.push("flate2") }
+    if cfg!(feature = "libflate") { names.push("libflate") }
+    if cfg!(feature = "zopfli") { names.push("zopfli") }
+    names
+}
+
+/// Gets a backend by name, set up with the specified level and strategy.
+/// Level and strategy are irrelevant to zopfli, which always does its best.
+#[allow(unused_variables)] // depends on compiled features.
+pub fn by_name(name: &str, level: u32, strategy: Strategy) -> Result<Box<dyn Codec>> {
+    let level = super::check_level(level)?;
+    match name {
+        #[cfg(feature = "flate2")]
+        "flate2" => Ok(Box::new(super::z_lib_flate2::Flate2Codec { level, strategy })),
+        #[cfg(feature = "libflate")]
+        "libflate" => Ok(Box::new(super::z_lib_libflate::LibflateCodec { level, strategy })),
+        #[cfg(feature = "zopfli")]
+        "zopfli" => Ok(Box::new(super::z_lib_zopfli::ZopfliCodec)),
+        _ => Err(IOError::new(ErrorKind::InvalidInput,
+                              format!("'{}' is not a compression backend of this program (available: {})",
+                                      name, names().join(", "))))
+    }
+}
+
+/// The default backend, the one aliased as `z_lib`.
+pub fn default_codec(level: u32, strategy: Strategy) -> Result<Box<dyn Codec>> {
+    by_name(names().first().cloned().unwrap_or("none"), level, strategy)
+}
+
+/// The default backend, when we only inflate: level and strategy do not matter.
+pub fn inflater() -> Result<Box<dyn Codec>> {
+    default_codec(super::MAX_LEVEL, Strategy::Default)
+}
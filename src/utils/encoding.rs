@@ -0,0 +1,191 @@
+extern crate lz4_flex;
+
+use self::lz4_flex::frame::{BlockSize, FrameDecoder, FrameEncoder, FrameInfo};
+use std::fmt;
+use std::fmt::Display;
+use std::io::{Cursor, Error as IOError, ErrorKind, Read, Result, Write};
+use super::codec::{Buffered, Codec, Sink};
+
+// Most devices store slots as zlib streams, but some vendor builds store raw framebuffers,
+// gzip or LZ4 blobs. The encoding is told by the first bytes of the blob.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// How a slot is stored in the logo image.
+pub enum Encoding {
+    Zlib,
+    Gzip,
+    /// LZ4 frame format.
+    Lz4,
+    /// Uncompressed device raster.
+    Raw,
+}
+
+static SUPPORTED_ENCODINGS: [(Encoding, &str); 4] = [
+    (Encoding::Zlib, "zlib"),
+    (Encoding::Gzip, "gzip"),
+    (Encoding::Lz4, "lz4"),
+    (Encoding::Raw, "raw")];
+
+const GZIP_MAGIC: [u8; 3] = [0x1F, 0x8B, 0x08];
+const LZ4_MAGIC: [u8; 4] = [0x04, 0x22, 0x4D, 0x18];
+
+impl Encoding {
+    /// Lists all supported encodings.
+    pub fn enumerate<'a>() -> Vec<&'a Encoding> {
+        SUPPORTED_ENCODINGS.iter().map(|(encoding, _)| encoding).collect()
+    }
+
+    /// Gets an encoding by its name.
+    pub fn by_name(name: &str) -> Result<Encoding> {
+        SUPPORTED_ENCODINGS.iter()
+            .find(|(_, encoding_name)| *encoding_name == name)
+            .map(|(encoding, _)| *encoding)
+            .ok_or_else(|| IOError::new(ErrorKind::InvalidInput,
+                                        format!("'{}' is not a supported encoding", name)))
+    }
+
+    /// Tells the encoding of a blob from its header, anything unknown is raw.
+    pub fn detect(blob: &[u8]) -> Encoding {
+        if blob.starts_with(&GZIP_MAGIC) {
+            Encoding::Gzip
+        } else if blob.starts_with(&LZ4_MAGIC) {
+            Encoding::Lz4
+        } else if is_zlib_header(blob) {
+            Encoding::Zlib
+        } else {
+            Encoding::Raw
+        }
+    }
+
+    /// Gets the device raster back from a blob.
+    pub fn decode(self, blob: &[u8], codec: &dyn Codec) -> Result<Vec<u8>> {
+        match self {
+            Encoding::Zlib => codec.inflate(blob),
+            Encoding::Gzip => codec.gunzip(blob),
+            Encoding::Lz4 => {
+                let mut decoder = FrameDecoder::new(blob);
+                let mut raster = Vec::new();
+                decoder.read_to_end(&mut raster).map(|_sz| raster)
+            }
+            Encoding::Raw => Ok(blob.to_vec()),
+        }
+    }
+
+    /// Turns a device raster into a blob. LZ4 has no notion of level.
+    pub fn encode(self, raster: &[u8], codec: &dyn Codec) -> Result<Vec<u8>> {
+        match self {
+            Encoding::Zlib => codec.deflate(raster),
+            Encoding::Gzip => codec.gzip(raster),
+            Encoding::Lz4 => {
+                let mut encoder = lz4_encoder();
+                encoder.write_all(raster)?;
+                encoder.finish().map_err(IOError::from)
+            }
+            Encoding::Raw => Ok(raster.to_vec()),
+        }
+    }
+
+    /// Reads the device raster back from a blob, as it goes when the encoding allows it.
+    pub fn decoder<'a>(self, blob: &'a [u8], codec: &dyn Codec) -> Result<Box<dyn Read + 'a>> {
+        match self {
+            Encoding::Zlib => codec.inflate_stream(blob),
+            Encoding::Gzip => Ok(Box::new(Cursor::new(codec.gunzip(blob)?))),
+            Encoding::Lz4 => Ok(Box::new(FrameDecoder::new(blob))),
+            Encoding::Raw => Ok(Box::new(blob)),
+        }
+    }
+
+    /// Writes a device raster into a blob, as it goes when the encoding allows it.
+    pub fn encoder<'a>(self, codec: &'a dyn Codec) -> Result<Box<dyn Sink + 'a>> {
+        match self {
+            Encoding::Zlib => codec.deflate_stream(),
+            Encoding::Gzip => Ok(Box::new(Buffered::new(move |raster: &[u8]| codec.gzip(raster)))),
+            Encoding::Lz4 => Ok(Box::new(lz4_encoder())),
+            Encoding::Raw => Ok(Box::new(Vec::new())),
+        }
+    }
+}
+
+/// LZ4 blocks are sized after the first write unless told otherwise: rows would make small blocks.
+/// Fixed 4MB blocks keep streams and buffers byte-identical, and memory bounded.
+fn lz4_encoder() -> FrameEncoder<Vec<u8>> {
+    FrameEncoder::with_frame_info(FrameInfo::new().block_size(BlockSize::Max4MB), Vec::new())
+}
+
+impl Sink for FrameEncoder<Vec<u8>> {
+    fn finish(self: Box<Self>) -> Result<Vec<u8>> {
+        FrameEncoder::finish(*self).map_err(IOError::from)
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match SUPPORTED_ENCODINGS.iter().find(|(encoding, _)| encoding == self) {
+            Some((_, name)) => f.write_str(name),
+            None => f.write_str("Encoding?") // should be unreachable.
+        }
+    }
+}
+
+/// Detects the encoding of a blob and decodes it.
+/// When the blob cannot be decoded as it looks, it's taken as a raw framebuffer.
+pub fn decode(blob: &[u8], codec: &dyn Codec) -> (Encoding, Vec<u8>) {
+    let encoding = Encoding::detect(blob);
+    match encoding.decode(blob, codec) {
+        Ok(raster) => (encoding, raster),
+        Err(_) => (Encoding::Raw, blob.to_vec()),
+    }
+}
+
+/// A zlib header is 'deflate' with a window of at most 32K, and a checksum.
+fn is_zlib_header(blob: &[u8]) -> bool {
+    match blob {
+        [cmf, flg, ..] => cmf & 0x0F == 8 && cmf >> 4 <= 7 && (*cmf as u16 * 256 + *flg as u16).is_multiple_of(31),
+        _ => false
+    }
+}
+
+/// Wraps a zlib stream as a gzip stream: both hold the same 'deflate' data, with other headers and checksums.
+pub fn zlib_to_gzip(zlib: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    if zlib.len() < 6 || !is_zlib_header(zlib) {
+        return Err(IOError::new(ErrorKind::InvalidData, "not a zlib stream"));
+    }
+    // no flags, no modification time, unknown OS.
+    let mut gzip = vec![0x1F, 0x8B, 0x08, 0, 0, 0, 0, 0, 0, 0xFF];
+    // drops zlib's 2 bytes header and adler32 trailer.
+    gzip.extend_from_slice(&zlib[2..zlib.len() - 4]);
+    gzip.extend_from_slice(&crc32(data).to_le_bytes());
+    gzip.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    Ok(gzip)
+}
+
+/// gzip's CRC-32 (IEEE polynomial, reflected).
+fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+        }
+        *entry = c;
+    }
+    !data.iter().fold(!0u32, |c, &b| table[((c ^ b as u32) & 0xFF) as usize] ^ (c >> 8))
+}
+
+#[test]
+fn test_detect_encodings() {
+    assert_eq!(Encoding::detect(&[0x78, 0x9C, 0x01]), Encoding::Zlib);
+    assert_eq!(Encoding::detect(&[0x78, 0xDA, 0x01]), Encoding::Zlib);
+    assert_eq!(Encoding::detect(&[0x1F, 0x8B, 0x08, 0x00]), Encoding::Gzip);
+    assert_eq!(Encoding::detect(&[0x04, 0x22, 0x4D, 0x18, 0x64]), Encoding::Lz4);
+    // a bad zlib checksum.
+    assert_eq!(Encoding::detect(&[0x78, 0x9D, 0x01]), Encoding::Raw);
+    assert_eq!(Encoding::detect(&[0xFF, 0xFF, 0xFF, 0xFF]), Encoding::Raw);
+    assert_eq!(Encoding::detect(&[]), Encoding::Raw);
+}
+
+#[test]
+fn test_crc32() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
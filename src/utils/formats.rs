@@ -0,0 +1,503 @@
+use std::fmt;
+use std::fmt::Display;
+use std::io::{Error as IOError, ErrorKind, Read, Result, Write};
+use super::byteorder::{BigEndian, ByteOrder, LittleEndian};
+use super::image;
+
+// PNG is the format of choice, but editors also give BMP, TGA or PPM files, and some tools want them.
+// These decoders read what editors commonly write, not every corner of each format.
+// Encoders write the simplest flavour: 32 bits BMP and TGA, binary PPM (which has no alpha).
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Image file formats, named after their extension.
+pub enum ImageFormat {
+    Png,
+    Bmp,
+    Tga,
+    /// Netpbm grayscale or color maps, ascii or binary.
+    Ppm,
+}
+
+static SUPPORTED_FORMATS: [(ImageFormat, &str); 4] = [
+    (ImageFormat::Png, "png"),
+    (ImageFormat::Bmp, "bmp"),
+    (ImageFormat::Tga, "tga"),
+    (ImageFormat::Ppm, "ppm")];
+
+/// Names of all supported formats.
+pub fn names() -> Vec<&'static str> {
+    SUPPORTED_FORMATS.iter().map(|(_, name)| *name).collect()
+}
+
+impl ImageFormat {
+    /// Lists all supported formats.
+    pub fn enumerate<'a>() -> Vec<&'a ImageFormat> {
+        SUPPORTED_FORMATS.iter().map(|(format, _)| format).collect()
+    }
+
+    /// Gets a format by its name, which is also its file extension.
+    pub fn by_name(name: &str) -> Result<ImageFormat> {
+        SUPPORTED_FORMATS.iter()
+            .find(|(_, format_name)| *format_name == name)
+            .map(|(format, _)| *format)
+            .ok_or_else(|| IOError::new(ErrorKind::InvalidInput,
+                                        format!("'{}' is not a supported image format", name)))
+    }
+
+    /// Reads an image of this format as a bytes buffer in the Rgba color mode.
+    pub fn read_rgba<R: Read>(self, mut reader: R) -> Result<(Vec<u8>, u32, u32)> {
+        if self == ImageFormat::Png {
+            return image::png_to_rgba(reader);
+        }
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        match self {
+            ImageFormat::Png => unreachable!(),
+            ImageFormat::Bmp => bmp_to_rgba(&data),
+            ImageFormat::Tga => tga_to_rgba(&data),
+            ImageFormat::Ppm => ppm_to_rgba(&data),
+        }
+    }
+
+    /// Writes a bytes buffer in the Rgba color mode as an image of this format.
+    pub fn write_rgba<W: Write>(self, mut writer: W, rgba: &[u8], w: u32, h: u32) -> Result<()> {
+        let rgba = bytes_at(rgba, 0, (w * h * 4) as usize)?;
+        match self {
+            ImageFormat::Png => image::rgba_to_png(writer, rgba, w, h),
+            ImageFormat::Bmp => writer.write_all(&rgba_to_bmp(rgba, w, h)),
+            ImageFormat::Tga => writer.write_all(&rgba_to_tga(rgba, w, h)?),
+            ImageFormat::Ppm => writer.write_all(&rgba_to_ppm(rgba, w, h)),
+        }
+    }
+}
+
+impl Display for ImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match SUPPORTED_FORMATS.iter().find(|(format, _)| format == self) {
+            Some((_, name)) => f.write_str(name),
+            None => f.write_str("ImageFormat?") // should be unreachable.
+        }
+    }
+}
+
+fn invalid<T>(message: String) -> Result<T> {
+    Err(IOError::new(ErrorKind::InvalidData, message))
+}
+
+fn unsupported<T>(message: String) -> Result<T> {
+    Err(IOError::new(ErrorKind::Unsupported, message))
+}
+
+/// Gets `len` bytes at `offset`, or fails if the file is too short.
+fn bytes_at(data: &[u8], offset: usize, len: usize) -> Result<&[u8]> {
+    offset.checked_add(len).and_then(|end| data.get(offset..end)).ok_or_else(
+        || IOError::new(ErrorKind::UnexpectedEof, format!("file is truncated at byte {}", offset.saturating_add(len))))
+}
+
+/// Scales a channel ranging up to `max` to 8 bits.
+fn scale_to_8(value: u32, max: u32) -> u8 {
+    if max == 0 { 0 } else { (value as u64 * 255 / max as u64) as u8 }
+}
+
+/// Extracts a channel told by a bit mask, scaled to 8 bits.
+fn masked(value: u32, mask: u32) -> Option<u8> {
+    if mask == 0 {
+        return None;
+    }
+    let shift = mask.trailing_zeros();
+    let max = mask >> shift;
+    Some(scale_to_8((value & mask) >> shift, max))
+}
+
+/// Reads a Windows bitmap: palettes of 1, 4 or 8 bits, 16, 24 and 32 bits pixels, with or without bit fields.
+/// Run-length encoded bitmaps are not supported.
+pub fn bmp_to_rgba(data: &[u8]) -> Result<(Vec<u8>, u32, u32)> {
+    let header = bytes_at(data, 0, 54)?;
+    if &header[0..2] != b"BM" {
+        return invalid("not a BMP file".to_string());
+    }
+    let pixels_offset = LittleEndian::read_u32(&header[10..]) as usize;
+    let dib_size = LittleEndian::read_u32(&header[14..]) as usize;
+    if dib_size < 40 {
+        return unsupported(format!("BMP header of {} bytes (OS/2 bitmap?)", dib_size));
+    }
+    let width = LittleEndian::read_i32(&header[18..]);
+    let height = LittleEndian::read_i32(&header[22..]);
+    let bpp = LittleEndian::read_u16(&header[28..]) as usize;
+    let compression = LittleEndian::read_u32(&header[30..]);
+    let colors = LittleEndian::read_u32(&header[46..]) as usize;
+    if width <= 0 || height == 0 {
+        return invalid(format!("BMP of {}x{} pixels", width, height));
+    }
+    // bitmaps are stored bottom-up, unless height is negative.
+    let (w, h, top_down) = (width as u32, height.unsigned_abs(), height < 0);
+    // red, green, blue, alpha masks.
+    let masks = match (compression, bpp) {
+        (0, 16) => [0x7C00, 0x03E0, 0x001F, 0],
+        (0, 24) | (0, 32) => [0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0xFF00_0000],
+        (0, 1) | (0, 4) | (0, 8) => [0; 4],
+        // bit fields follow the 40 bytes header, or are part of larger headers.
+        (3, 16) | (3, 32) | (6, 16) | (6, 32) => {
+            let fields = bytes_at(data, 54, 16)?;
+            let has_alpha = compression == 6 || dib_size >= 56;
+            [LittleEndian::read_u32(&fields[0..]),
+                LittleEndian::read_u32(&fields[4..]),
+                LittleEndian::read_u32(&fields[8..]),
+                if has_alpha { LittleEndian::read_u32(&fields[12..]) } else { 0 }]
+        }
+        _ => return unsupported(format!("BMP of {} bits per pixel with compression {}", bpp, compression)),
+    };
+    let palette = if bpp <= 8 {
+        let count = if colors == 0 { 1 << bpp } else { colors };
+        bytes_at(data, 14 + dib_size, count * 4)?
+    } else {
+        &[]
+    };
+    // rows are padded to 4 bytes, they must all be there before pixels are allocated.
+    let stride = (bpp as u64 * w as u64).div_ceil(32) * 4;
+    match stride.checked_mul(h as u64) {
+        Some(len) if pixels_offset as u64 + len <= data.len() as u64 => (),
+        _ => return invalid(format!("BMP of {}x{} pixels does not fit its {} bytes", w, h, data.len())),
+    }
+    let stride = stride as usize;
+    let mut rgba = Vec::with_capacity(w as usize * h as usize * 4);
+    for y in 0..h as usize {
+        let row = if top_down { y } else { h as usize - 1 - y };
+        let row = bytes_at(data, pixels_offset + row * stride, stride)?;
+        for x in 0..w as usize {
+            match bpp {
+                1 | 4 | 8 => {
+                    let bit = x * bpp;
+                    let index = ((row[bit / 8] >> (8 - bpp - bit % 8)) & ((1 << bpp) - 1) as u8) as usize;
+                    let color = palette.get(index * 4..index * 4 + 4).ok_or_else(
+                        || IOError::new(ErrorKind::InvalidData, format!("BMP color {} is not in the palette", index)))?;
+                    rgba.extend_from_slice(&[color[2], color[1], color[0], 0xFF]);
+                }
+                24 => rgba.extend_from_slice(&[row[x * 3 + 2], row[x * 3 + 1], row[x * 3], 0xFF]),
+                _ => {
+                    let value = if bpp == 16 {
+                        LittleEndian::read_u16(&row[x * 2..]) as u32
+                    } else {
+                        LittleEndian::read_u32(&row[x * 4..])
+                    };
+                    rgba.extend_from_slice(&[masked(value, masks[0]).unwrap_or(0),
+                        masked(value, masks[1]).unwrap_or(0),
+                        masked(value, masks[2]).unwrap_or(0),
+                        masked(value, masks[3]).unwrap_or(0xFF)]);
+                }
+            }
+        }
+    }
+    // most 32 bits bitmaps leave the 4th byte to zero: they are opaque, not transparent.
+    if compression == 0 && bpp == 32 && rgba.iter().skip(3).step_by(4).all(|&a| a == 0) {
+        image::strip_alpha(&mut rgba);
+    }
+    Ok((rgba, w, h))
+}
+
+/// Writes a 32 bits bitmap, with a version 4 header to tell where alpha is.
+pub fn rgba_to_bmp(rgba: &[u8], w: u32, h: u32) -> Vec<u8> {
+    let offset = 14 + 108;
+    let size = offset + rgba.len();
+    let mut bmp = Vec::with_capacity(size);
+    bmp.extend_from_slice(b"BM");
+    for field in [size as u32, 0, offset as u32, 108, w, h].iter() {
+        bmp.extend_from_slice(&field.to_le_bytes());
+    }
+    // 1 plane, 32 bits per pixel, bit fields.
+    bmp.extend_from_slice(&[1, 0, 32, 0, 3, 0, 0, 0]);
+    for field in [rgba.len() as u32, 2835, 2835, 0, 0, 0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0xFF00_0000].iter() {
+        bmp.extend_from_slice(&field.to_le_bytes());
+    }
+    // sRGB, no need for end points nor gamma.
+    bmp.extend_from_slice(b"BGRs");
+    bmp.resize(offset, 0);
+    // bottom-up rows of |b|g|r|a| pixels.
+    for row in rgba.chunks_exact((w * 4) as usize).rev() {
+        bmp.extend(row.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]]));
+    }
+    bmp
+}
+
+/// Writes a 32 bits true color TGA, top-down.
+pub fn rgba_to_tga(rgba: &[u8], w: u32, h: u32) -> Result<Vec<u8>> {
+    if w > 0xFFFF || h > 0xFFFF {
+        return unsupported(format!("TGA of {}x{} pixels", w, h));
+    }
+    let mut tga = vec![0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    tga.extend_from_slice(&(w as u16).to_le_bytes());
+    tga.extend_from_slice(&(h as u16).to_le_bytes());
+    // 32 bits per pixel, 8 of alpha, top-down.
+    tga.extend_from_slice(&[32, 0x28]);
+    tga.extend(rgba.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]]));
+    Ok(tga)
+}
+
+/// Writes a binary pix map, dropping alpha.
+pub fn rgba_to_ppm(rgba: &[u8], w: u32, h: u32) -> Vec<u8> {
+    let mut ppm = format!("P6\n{} {}\n255\n", w, h).into_bytes();
+    ppm.extend(rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]));
+    ppm
+}
+
+/// Gets a TGA color, stored as little endian B, G, R and A channels.
+fn tga_color(pixel: &[u8], bits: u8, alpha_bits: u8) -> [u8; 4] {
+    match bits {
+        8 => [pixel[0], pixel[0], pixel[0], 0xFF],
+        15 | 16 => {
+            let value = LittleEndian::read_u16(pixel) as u32;
+            let alpha = if bits == 16 && alpha_bits > 0 && value & 0x8000 == 0 { 0 } else { 0xFF };
+            [scale_to_8((value >> 10) & 0x1F, 0x1F), scale_to_8((value >> 5) & 0x1F, 0x1F),
+                scale_to_8(value & 0x1F, 0x1F), alpha]
+        }
+        24 => [pixel[2], pixel[1], pixel[0], 0xFF],
+        _ => [pixel[2], pixel[1], pixel[0], if alpha_bits > 0 { pixel[3] } else { 0xFF }],
+    }
+}
+
+/// Reads a Truevision TGA image: color mapped, true color or grayscale, run-length encoded or not.
+pub fn tga_to_rgba(data: &[u8]) -> Result<(Vec<u8>, u32, u32)> {
+    let header = bytes_at(data, 0, 18)?;
+    let (id_length, map_type, image_type) = (header[0] as usize, header[1], header[2]);
+    let map_first = LittleEndian::read_u16(&header[3..]) as usize;
+    let map_length = LittleEndian::read_u16(&header[5..]) as usize;
+    let map_bits = header[7];
+    let (w, h) = (LittleEndian::read_u16(&header[12..]) as u32, LittleEndian::read_u16(&header[14..]) as u32);
+    let (bits, descriptor) = (header[16], header[17]);
+    let alpha_bits = descriptor & 0x0F;
+    let supported = match image_type & !8 {
+        1 => map_type == 1 && (bits == 8 || bits == 16) && [15, 16, 24, 32].contains(&map_bits),
+        2 => [15, 16, 24, 32].contains(&bits),
+        3 => bits == 8 || bits == 16,
+        _ => false,
+    };
+    if !supported {
+        return unsupported(format!("TGA image type {} of {} bits per pixel", image_type, bits));
+    }
+    let map_entry = (map_bits as usize).div_ceil(8);
+    let map_offset = 18 + id_length;
+    let map = if map_type == 1 { bytes_at(data, map_offset, map_length * map_entry)? } else { &[] };
+    // gathers raw pixels, unpacking runs.
+    let pixel_size = (bits as usize).div_ceil(8);
+    let pixels_len = w as usize * h as usize * pixel_size;
+    let mut offset = map_offset + map.len();
+    let pixels = if image_type & 8 == 0 {
+        bytes_at(data, offset, pixels_len)?.to_vec()
+    } else {
+        // a packet of at least 2 bytes repeats a pixel 128 times at most.
+        if pixels_len as u64 > data.len().saturating_sub(offset) as u64 / 2 * 128 * pixel_size as u64 {
+            return invalid(format!("TGA of {}x{} pixels does not fit its {} bytes", w, h, data.len()));
+        }
+        let mut pixels = Vec::with_capacity(pixels_len);
+        while pixels.len() < pixels_len {
+            let packet = bytes_at(data, offset, 1)?[0];
+            let count = (packet & 0x7F) as usize + 1;
+            offset += 1;
+            if packet & 0x80 != 0 {
+                let pixel = bytes_at(data, offset, pixel_size)?;
+                for _ in 0..count { pixels.extend_from_slice(pixel) };
+                offset += pixel_size;
+            } else {
+                pixels.extend_from_slice(bytes_at(data, offset, count * pixel_size)?);
+                offset += count * pixel_size;
+            }
+        }
+        pixels.truncate(pixels_len);
+        pixels
+    };
+    let mut rgba = Vec::with_capacity(w as usize * h as usize * 4);
+    // images are stored bottom-up, left to right, unless told otherwise.
+    let (top_down, right_to_left) = (descriptor & 0x20 != 0, descriptor & 0x10 != 0);
+    for y in 0..h {
+        let row = if top_down { y } else { h - 1 - y };
+        for x in 0..w {
+            let column = if right_to_left { w - 1 - x } else { x };
+            let at = ((row * w + column) as usize) * pixel_size;
+            let pixel = &pixels[at..at + pixel_size];
+            let color = match image_type & !8 {
+                1 => {
+                    let index = if bits == 8 { pixel[0] as usize } else { LittleEndian::read_u16(pixel) as usize };
+                    let entry = index.checked_sub(map_first)
+                        .and_then(|index| map.get(index * map_entry..(index + 1) * map_entry))
+                        .ok_or_else(|| IOError::new(ErrorKind::InvalidData,
+                                                    format!("TGA color {} is not in the color map", index)))?;
+                    tga_color(entry, map_bits, alpha_bits)
+                }
+                3 if bits == 16 => [pixel[0], pixel[0], pixel[0], pixel[1]],
+                _ => tga_color(pixel, bits, alpha_bits),
+            };
+            rgba.extend_from_slice(&color);
+        }
+    }
+    Ok((rgba, w, h))
+}
+
+/// Splits a Netpbm header into tokens, skipping whitespace and comments.
+struct PnmTokens<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> PnmTokens<'a> {
+    fn next_number(&mut self) -> Result<u32> {
+        loop {
+            match self.data.get(self.offset) {
+                Some(b'#') => while self.data.get(self.offset).is_some_and(|&c| c != b'\n') { self.offset += 1 },
+                Some(c) if c.is_ascii_whitespace() => self.offset += 1,
+                _ => break,
+            }
+        }
+        let start = self.offset;
+        while self.data.get(self.offset).is_some_and(|c| c.is_ascii_digit()) {
+            self.offset += 1;
+        }
+        std::str::from_utf8(&self.data[start..self.offset]).ok()
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| IOError::new(ErrorKind::InvalidData, format!("expected a number at byte {}", start)))
+    }
+}
+
+/// Reads a Netpbm gray map (P2, P5) or pix map (P3, P6), with samples of 8 or 16 bits.
+pub fn ppm_to_rgba(data: &[u8]) -> Result<(Vec<u8>, u32, u32)> {
+    let magic = bytes_at(data, 0, 2)?;
+    let (channels, binary) = match magic {
+        b"P2" => (1, false),
+        b"P3" => (3, false),
+        b"P5" => (1, true),
+        b"P6" => (3, true),
+        _ => return unsupported(format!("Netpbm magic '{}'", String::from_utf8_lossy(magic))),
+    };
+    let mut tokens = PnmTokens { data, offset: 2 };
+    let (w, h, max) = (tokens.next_number()?, tokens.next_number()?, tokens.next_number()?);
+    if max == 0 || max > 0xFFFF {
+        return invalid(format!("Netpbm maximum value {}", max));
+    }
+    // a sample takes a byte at least, or two: the raster must fit the file before it's collected.
+    let sample_size = if binary && max >= 0x100 { 2 } else { 1 };
+    let samples_len = match (w as usize).checked_mul(h as usize).and_then(|pixels| pixels.checked_mul(channels)) {
+        Some(len) if len.checked_mul(sample_size).is_some_and(|size| size <= data.len()) => len,
+        _ => return invalid(format!("Netpbm image of {}x{} pixels does not fit its {} bytes", w, h, data.len())),
+    };
+    let samples: Vec<u8> = if binary {
+        // a single whitespace separates the header from the raster.
+        let start = tokens.offset + 1;
+        if max < 0x100 {
+            bytes_at(data, start, samples_len)?.iter().map(|&s| scale_to_8(s as u32, max)).collect()
+        } else {
+            bytes_at(data, start, samples_len * 2)?.chunks_exact(2)
+                .map(|s| scale_to_8(BigEndian::read_u16(s) as u32, max)).collect()
+        }
+    } else {
+        (0..samples_len).map(|_| tokens.next_number().map(|s| scale_to_8(s.min(max), max)))
+            .collect::<Result<Vec<u8>>>()?
+    };
+    let rgba = if channels == 1 {
+        samples.iter().flat_map(|&g| [g, g, g, 0xFF]).collect()
+    } else {
+        samples.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 0xFF]).collect()
+    };
+    Ok((rgba, w, h))
+}
+
+#[cfg(test)]
+const TEST_RGBA: [u8; 16] = [0xFF, 0, 0, 0xFF, 0, 0xFF, 0, 0xFF, 0, 0, 0xFF, 0xFF, 0x80, 0x80, 0x80, 0xFF];
+
+#[test]
+fn test_bmp_to_rgba() {
+    // 2x2 pixels, 24 bits, bottom-up rows padded to 8 bytes.
+    let mut bmp = b"BM".to_vec();
+    bmp.extend_from_slice(&[70, 0, 0, 0, 0, 0, 0, 0, 54, 0, 0, 0]);
+    bmp.extend_from_slice(&[40, 0, 0, 0, 2, 0, 0, 0, 2, 0, 0, 0, 1, 0, 24, 0]);
+    bmp.extend_from_slice(&[0; 24]);
+    bmp.extend_from_slice(&[0xFF, 0, 0, 0x80, 0x80, 0x80, 0, 0]);
+    bmp.extend_from_slice(&[0, 0, 0xFF, 0, 0xFF, 0, 0, 0]);
+    assert_eq!(bmp_to_rgba(&bmp).unwrap(), (TEST_RGBA.to_vec(), 2, 2));
+    // the same, top-down in 32 bits with alpha bit fields, and some transparency.
+    let mut bmp = bmp[..54].to_vec();
+    bmp[22..26].copy_from_slice(&(-2i32).to_le_bytes());
+    bmp[28] = 32;
+    bmp[30] = 6;
+    bmp[10] = 70;
+    // masks pick channels in little endian words: bytes go |r|g|b|a|.
+    for mask in [0x0000_00FFu32, 0x0000_FF00, 0x00FF_0000, 0xFF00_0000].iter() {
+        bmp.extend_from_slice(&mask.to_le_bytes());
+    }
+    let mut expected = TEST_RGBA.to_vec();
+    expected[3] = 0x40;
+    bmp.extend_from_slice(&expected);
+    assert_eq!(bmp_to_rgba(&bmp).unwrap(), (expected, 2, 2));
+    assert_eq!(bmp_to_rgba(&bmp[..60]).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    // pixels are told missing before they are allocated.
+    assert_eq!(bmp_to_rgba(&bmp[..80]).unwrap_err().kind(), ErrorKind::InvalidData);
+    // a 24 bits header alone, claiming huge dimensions.
+    let mut huge = bmp[..54].to_vec();
+    huge[28] = 24;
+    huge[30] = 0;
+    huge[18..22].copy_from_slice(&40000i32.to_le_bytes());
+    huge[22..26].copy_from_slice(&40000i32.to_le_bytes());
+    assert_eq!(bmp_to_rgba(&huge).unwrap_err().kind(), ErrorKind::InvalidData);
+    huge[18..22].copy_from_slice(&i32::MAX.to_le_bytes());
+    huge[22..26].copy_from_slice(&i32::MIN.to_le_bytes());
+    assert_eq!(bmp_to_rgba(&huge).unwrap_err().kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_tga_to_rgba() {
+    // 2x2 pixels, run-length encoded 32 bits, top-down.
+    let mut tga = vec![0, 0, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 2, 0, 32, 0x28];
+    // red, then green, as raw pixels.
+    tga.extend_from_slice(&[0x01, 0, 0, 0xFF, 0xFF, 0, 0xFF, 0, 0xFF]);
+    // blue, then gray, as runs of 1 pixel.
+    tga.extend_from_slice(&[0x80, 0xFF, 0, 0, 0xFF, 0x80, 0x80, 0x80, 0x80, 0xFF]);
+    assert_eq!(tga_to_rgba(&tga).unwrap(), (TEST_RGBA.to_vec(), 2, 2));
+    // the same, color mapped, bottom-up, 24 bits colors.
+    let mut tga = vec![0, 1, 1, 0, 0, 4, 0, 24, 0, 0, 0, 0, 2, 0, 2, 0, 8, 0];
+    tga.extend_from_slice(&[0, 0, 0xFF, 0, 0xFF, 0, 0xFF, 0, 0, 0x80, 0x80, 0x80]);
+    tga.extend_from_slice(&[2, 3, 0, 1]);
+    assert_eq!(tga_to_rgba(&tga).unwrap(), (TEST_RGBA.to_vec(), 2, 2));
+    tga[2] = 4;
+    assert_eq!(tga_to_rgba(&tga).unwrap_err().kind(), ErrorKind::Unsupported);
+    // a few runs cannot make 65535x65535 pixels.
+    let mut huge = vec![0, 0, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 32, 0x28];
+    huge.extend_from_slice(&[0xFF, 0, 0, 0xFF, 0xFF]);
+    assert_eq!(tga_to_rgba(&huge).unwrap_err().kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_ppm_to_rgba() {
+    let ascii = b"P3\n# a comment\n2 2\n255\n255 0 0  0 255 0\n0 0 255  128 128 128\n";
+    assert_eq!(ppm_to_rgba(ascii).unwrap(), (TEST_RGBA.to_vec(), 2, 2));
+    let mut binary = b"P6 2 2 65535\n".to_vec();
+    for sample in [0xFFFFu16, 0, 0, 0, 0xFFFF, 0, 0, 0, 0xFFFF, 0x8080, 0x8080, 0x8080].iter() {
+        binary.extend_from_slice(&sample.to_be_bytes());
+    }
+    assert_eq!(ppm_to_rgba(&binary).unwrap(), (TEST_RGBA.to_vec(), 2, 2));
+    let gray = b"P5 2 1 15\n\x0F\x00";
+    assert_eq!(ppm_to_rgba(gray).unwrap(), (vec![0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0xFF], 2, 1));
+    assert_eq!(ppm_to_rgba(b"P6 2 2 255\n\x00").unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    // samples are told missing before they are collected.
+    assert_eq!(ppm_to_rgba(b"P6 65536 65536 255\n\x00").unwrap_err().kind(), ErrorKind::InvalidData);
+    assert_eq!(ppm_to_rgba(b"P3 65536 65536 255\n0 0").unwrap_err().kind(), ErrorKind::InvalidData);
+    assert_eq!(ppm_to_rgba(b"P5 4294967295 4294967295 65535\n").unwrap_err().kind(), ErrorKind::InvalidData);
+    for format in ImageFormat::enumerate() {
+        assert_eq!(ImageFormat::by_name(&format!("{}", format)).unwrap(), *format);
+    }
+}
+
+#[test]
+fn test_formats_round_trip() {
+    let mut rgba = TEST_RGBA.to_vec();
+    rgba[7] = 0x40;
+    for format in ImageFormat::enumerate() {
+        let mut file = Vec::new();
+        format.write_rgba(&mut file, &rgba, 2, 2).unwrap();
+        let (read, w, h) = format.read_rgba(&file as &[u8]).unwrap();
+        assert_eq!((w, h), (2, 2), "{}", format);
+        if *format == ImageFormat::Ppm {
+            assert_eq!(read, TEST_RGBA.to_vec());
+        } else {
+            assert_eq!(read, rgba, "{}", format);
+        }
+        assert!(format.write_rgba(&mut file, &rgba[..12], 2, 2).is_err());
+    }
+}
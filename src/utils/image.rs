@@ -1,7 +1,10 @@
-use std::io::{Cursor, Error as IOError, ErrorKind, Read, Result, Write};
-use super::byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::fmt;
+use std::fmt::Display;
+use std::io::{Error as IOError, ErrorKind, Read, Result, Write};
+use super::byteorder::{BigEndian, ByteOrder, LittleEndian};
+#[cfg(test)]
+use super::byteorder::ReadBytesExt;
 use super::png;
-use super::png::HasParameters;
 use super::super::{ColorMode, Endian};
 
 pub trait ImageIO {
@@ -29,24 +32,22 @@ impl ImageIO for ColorMode {
     /// Converts some image in RGBA, BigEndian format to device specific data.
     fn rgba_to_device(&self, rgba: &[u8], w: u32, h: u32) -> Result<Vec<u8>> {
         match self {
-            &ColorMode::Rgba(Endian::Big) => Ok(Vec::from(rgba.clone())),
-            &ColorMode::Rgba(Endian::Little) => u32be_to_u32le(&rgba as &[u8], (w*h) as usize),
-            &ColorMode::Bgra(Endian::Big) => rgba_to_bgra::<BigEndian, _>(&rgba as &[u8], w, h),
-            &ColorMode::Bgra(Endian::Little) => rgba_to_bgra::<LittleEndian, _>(&rgba as &[u8], w, h),
-            &ColorMode::Rgb565(Endian::Big) =>
-                rgba_to_rgb565::<BigEndian, _>(&rgba as &[u8], w, h),
-            &ColorMode::Rgb565(Endian::Little) =>
-                rgba_to_rgb565::<LittleEndian, _>(&rgba as &[u8], w, h),
+            &ColorMode::Rgba(Endian::Big) => pixels_of(rgba, w, h, 4).map(Vec::from),
+            &ColorMode::Rgba(Endian::Little) => u32be_to_u32le(rgba, (w*h) as usize),
+            &ColorMode::Bgra(Endian::Big) => rgba_to_bgra::<BigEndian>(rgba, w, h),
+            &ColorMode::Bgra(Endian::Little) => rgba_to_bgra::<LittleEndian>(rgba, w, h),
+            &ColorMode::Rgb565(Endian::Big) => rgba_to_rgb565::<BigEndian>(rgba, w, h),
+            &ColorMode::Rgb565(Endian::Little) => rgba_to_rgb565::<LittleEndian>(rgba, w, h),
         }
     }
 
     /// Converts some device specific image data to RGBA, BigEndian format.
     fn device_to_rgba(&self, device: &[u8], w: u32, h: u32) -> Result<Vec<u8>> {
         match self {
-            &ColorMode::Rgba(Endian::Big) => Ok(Vec::from(device.clone())),
-            &ColorMode::Rgba(Endian::Little) => u32be_to_u32le(&device as &[u8], device.len()),
-            &ColorMode::Bgra(Endian::Big) => rgba_to_bgra::<BigEndian, _>(&device as &[u8], w, h),
-            &ColorMode::Bgra(Endian::Little) => rgba_to_bgra::<LittleEndian, _>(&device as &[u8], w, h),
+            &ColorMode::Rgba(Endian::Big) => pixels_of(device, w, h, 4).map(Vec::from),
+            &ColorMode::Rgba(Endian::Little) => u32be_to_u32le(device, (w*h) as usize),
+            &ColorMode::Bgra(Endian::Big) => bgra_to_rgba::<BigEndian>(device, w, h),
+            &ColorMode::Bgra(Endian::Little) => bgra_to_rgba::<LittleEndian>(device, w, h),
             &ColorMode::Rgb565(Endian::Big) => rgb565_to_rgba::<BigEndian>(&device, w, h),
             &ColorMode::Rgb565(Endian::Little) => rgb565_to_rgba::<LittleEndian>(&device, w, h),
         }
@@ -55,13 +56,32 @@ impl ImageIO for ColorMode {
 
 /// Reads a PNG source as bytes buffer the Rgba color mode.
 pub fn png_to_rgba<R: Read>(reader: R) -> Result<(Vec<u8>, u32, u32)> {
-    let decoder = png::Decoder::new(reader);
-    let (info, mut png_reader) = decoder.read_info()?;
+    let mut decoder = png::Decoder::new(reader);
+    // palettes are expanded, 16 bits channels are reduced to 8 bits.
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut png_reader = decoder.read_info()?;
     // Allocate the output buffer.
-    let mut buf = vec![0; info.buffer_size()];
+    let mut buf = vec![0; png_reader.output_buffer_size()];
     // png is supposed to contain a single frame.
-    png_reader.next_frame(&mut buf)?;
-    Ok((buf, info.width, info.height))
+    let info = png_reader.next_frame(&mut buf)?;
+    buf.truncate(info.buffer_size());
+    // grayscale and rgb images from editors are brought to rgba.
+    png_pixels_to_rgba(&buf, info.color_type, info.bit_depth).map(|rgba| (rgba, info.width, info.height))
+}
+
+/// Brings decoded PNG pixels of any color type to RGBA.
+/// Palettes and 16 bits channels must have been expanded and stripped by the decoder.
+pub fn png_pixels_to_rgba(data: &[u8], color_type: png::ColorType, bit_depth: png::BitDepth) -> Result<Vec<u8>> {
+    if bit_depth != png::BitDepth::Eight {
+        return Err(IOError::new(ErrorKind::Unsupported, format!("{:?} bits PNG channels", bit_depth)));
+    }
+    match color_type {
+        png::ColorType::Rgba => Ok(data.to_vec()),
+        png::ColorType::Rgb => Ok(data.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 0xFF]).collect()),
+        png::ColorType::GrayscaleAlpha => Ok(data.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect()),
+        png::ColorType::Grayscale => Ok(data.iter().flat_map(|&g| [g, g, g, 0xFF]).collect()),
+        png::ColorType::Indexed => Err(IOError::new(ErrorKind::Unsupported, "PNG palette was not expanded")),
+    }
 }
 
 /// Clears the alpha channel (set to 0xFF).
@@ -79,58 +99,189 @@ pub fn strip_alpha(data: &mut [u8]) {
 
 /// Writes an Rgba color mode byte buffer as PNG.
 pub fn rgba_to_png<W: Write>(writer: W, data: &[u8], w: u32, h: u32) -> Result<()> {
+    let mut png_writer = rgba_png_encoder(writer, w, h).write_header()?;
+    png_writer.write_image_data(data).map_err(|e| IOError::new(ErrorKind::InvalidData, e.to_string()))
+}
+
+/// A PNG encoder for Rgba color mode byte buffers.
+pub fn rgba_png_encoder<'a, W: Write>(writer: W, w: u32, h: u32) -> png::Encoder<'a, W> {
     let mut encoder = png::Encoder::new(writer, w, h);
-    encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
-    let mut png_writer = encoder.write_header()?;
-    png_writer.write_image_data(&data).map_err(|e| IOError::new(ErrorKind::InvalidData, e.to_string()))
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
 }
 
-/// Converts RGBA byte buffer to Rgb565 with the specified endianness.
-pub fn rgba_to_bgra<O: ByteOrder, R: Read>(mut reader: R, w: u32, h: u32) -> Result<(Vec<u8>)> {
-    let pixels = (w * h) as usize;
-    let mut rgb565: Vec<u8> = Vec::with_capacity(pixels * 4);
-    for _ in 0..pixels {
+/// Scales an RGBA raster of `w` x `h` pixels to `nw` x `nh` pixels (nearest neighbour).
+/// That's crude, but good enough for previews.
+pub fn scale_nearest(rgba: &[u8], w: u32, h: u32, nw: u32, nh: u32) -> Vec<u8> {
+    let (w, h, nw, nh) = (w as usize, h as usize, nw as usize, nh as usize);
+    let mut scaled = Vec::with_capacity(nw * nh * 4);
+    for y in 0..nh {
+        let sy = y * h / nh;
+        for x in 0..nw {
+            let source = (sy * w + x * w / nw) * 4;
+            scaled.extend_from_slice(&rgba[source..source + 4]);
+        }
+    }
+    scaled
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Pixel transforms, turning the raster stored in the device into an upright image.
+/// Rotations are clockwise.
+pub enum Transform {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    MirrorH,
+    MirrorV,
+}
+
+static SUPPORTED_TRANSFORMS: [(Transform, &str); 6] = [
+    (Transform::Identity, "none"),
+    (Transform::Rotate90, "rot90"),
+    (Transform::Rotate180, "rot180"),
+    (Transform::Rotate270, "rot270"),
+    (Transform::MirrorH, "mirrorh"),
+    (Transform::MirrorV, "mirrorv")];
+
+impl Transform {
+    /// Lists all managed transforms.
+    pub fn enumerate<'a>() -> Vec<&'a Transform> {
+        SUPPORTED_TRANSFORMS.iter().map(|(transform, _name)| transform).collect()
+    }
+
+    pub fn by_name(name: &str) -> Result<Transform> {
+        SUPPORTED_TRANSFORMS.iter()
+            .find(|(_, n)| name.eq(*n))
+            .map(|(transform, _)| *transform)
+            .ok_or_else(|| IOError::new(
+                ErrorKind::InvalidInput, format!("{} is not a transform", name)))
+    }
+
+    /// The transform which reverts this one (from upright image back to device raster).
+    pub fn inverse(&self) -> Transform {
+        match *self {
+            Transform::Rotate90 => Transform::Rotate270,
+            Transform::Rotate270 => Transform::Rotate90,
+            other => other,
+        }
+    }
+
+    /// Dimensions of the image once transformed.
+    pub fn dimensions(&self, w: u32, h: u32) -> (u32, u32) {
+        match *self {
+            Transform::Rotate90 | Transform::Rotate270 => (h, w),
+            _ => (w, h),
+        }
+    }
+
+    /// Applies this transform to an RGBA raster of `w` x `h` pixels.
+    /// Returns the transformed raster along with its new width and height.
+    pub fn apply(&self, rgba: &[u8], w: u32, h: u32) -> Result<(Vec<u8>, u32, u32)> {
+        let (tw, th) = self.dimensions(w, h);
+        if *self == Transform::Identity {
+            return Ok((Vec::from(rgba), tw, th));
+        }
+        let (w, h) = (w as usize, h as usize);
+        if rgba.len() != w * h * 4 {
+            return Err(IOError::new(ErrorKind::InvalidData, format!(
+                "{} bytes is not a {}x{} RGBA raster", rgba.len(), w, h)));
+        }
+        let mut transformed = vec![0; rgba.len()];
+        let mut offset = 0;
+        // walks the destination raster, and picks the matching source pixel.
+        for y in 0..(th as usize) {
+            for x in 0..(tw as usize) {
+                let (sx, sy) = match *self {
+                    Transform::Rotate90 => (y, h - 1 - x),
+                    Transform::Rotate180 => (w - 1 - x, h - 1 - y),
+                    Transform::Rotate270 => (w - 1 - y, x),
+                    Transform::MirrorH => (w - 1 - x, y),
+                    Transform::MirrorV => (x, h - 1 - y),
+                    Transform::Identity => (x, y),
+                };
+                let source = (sy * w + sx) * 4;
+                transformed[offset..offset + 4].copy_from_slice(&rgba[source..source + 4]);
+                offset += 4;
+            }
+        }
+        Ok((transformed, tw, th))
+    }
+}
+
+impl Display for Transform {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match SUPPORTED_TRANSFORMS.iter().find(|(transform, _)| transform.eq(self)) {
+            Some((_, name)) => fmt.write_str(name),
+            None => fmt.write_str("Transform?") // should be unreachable.
+        }
+    }
+}
+
+/// Gives the first `w` x `h` pixels of a raster, failing if it's too short.
+fn pixels_of(data: &[u8], w: u32, h: u32, bytes_per_pixel: usize) -> Result<&[u8]> {
+    let len = (w as usize) * (h as usize) * bytes_per_pixel;
+    data.get(..len).ok_or_else(|| IOError::new(ErrorKind::UnexpectedEof, format!(
+        "{} bytes is too short for {}x{} pixels of {} bytes", data.len(), w, h, bytes_per_pixel)))
+}
+
+// Kernels below walk whole slices in fixed size chunks, without bound checks nor I/O calls per pixel,
+// so that the compiler can vectorise them.
+
+/// Converts RGBA byte buffer to Bgra with the specified endianness.
+pub fn rgba_to_bgra<O: ByteOrder>(rgba: &[u8], w: u32, h: u32) -> Result<Vec<u8>> {
+    let rgba = pixels_of(rgba, w, h, 4)?;
+    let mut bgra = vec![0; rgba.len()];
+    for (dst, src) in bgra.chunks_exact_mut(4).zip(rgba.chunks_exact(4)) {
         // 'pivot' rgba is always BigEndian.
-        let color32 = reader.read_u32::<BigEndian>()? as u32;
-        rgb565.write_u32::<O>(rgba2bgra(color32))?;
+        O::write_u32(dst, rgba2bgra(BigEndian::read_u32(src)));
     }
-    Ok(rgb565)
+    Ok(bgra)
 }
 
-/// Converts RGBA Big Endian to RGBA LittleEndian. It works also the other way round...
-pub fn u32be_to_u32le<R: Read>(mut reader: R, words: usize) -> Result<(Vec<u8>)> {
-    let mut rgbale: Vec<u8> = Vec::with_capacity(words);
-    for _ in 0..words {
+/// Converts Bgra with the specified endianness to RGBA byte buffer.
+pub fn bgra_to_rgba<O: ByteOrder>(bgra: &[u8], w: u32, h: u32) -> Result<Vec<u8>> {
+    let bgra = pixels_of(bgra, w, h, 4)?;
+    let mut rgba = vec![0; bgra.len()];
+    for (dst, src) in rgba.chunks_exact_mut(4).zip(bgra.chunks_exact(4)) {
         // 'pivot' rgba is always BigEndian.
-        let color32 = reader.read_u32::<BigEndian>()? as u32;
-        rgbale.write_u32::<LittleEndian>(rgba2bgra(color32))?;
+        BigEndian::write_u32(dst, rgba2bgra(O::read_u32(src)));
+    }
+    Ok(rgba)
+}
+
+/// Converts RGBA Big Endian to RGBA LittleEndian, a plain byte swap of each word.
+/// It works also the other way round...
+pub fn u32be_to_u32le(data: &[u8], words: usize) -> Result<Vec<u8>> {
+    let data = pixels_of(data, words as u32, 1, 4)?;
+    let mut swapped = vec![0; data.len()];
+    for (dst, src) in swapped.chunks_exact_mut(4).zip(data.chunks_exact(4)) {
+        LittleEndian::write_u32(dst, BigEndian::read_u32(src));
     }
-    Ok(rgbale)
+    Ok(swapped)
 }
 
 /// Converts RGBA byte buffer to Rgb565 with the specified endianness.
-pub fn rgba_to_rgb565<O: ByteOrder, R: Read>(mut reader: R, w: u32, h: u32) -> Result<(Vec<u8>)> {
-    let pixels = (w * h) as usize;
-    let mut rgb565: Vec<u8> = Vec::with_capacity(pixels * 2);
-    for _ in 0..pixels {
+pub fn rgba_to_rgb565<O: ByteOrder>(rgba: &[u8], w: u32, h: u32) -> Result<Vec<u8>> {
+    let rgba = pixels_of(rgba, w, h, 4)?;
+    let mut rgb565 = vec![0; rgba.len() / 2];
+    for (dst, src) in rgb565.chunks_exact_mut(2).zip(rgba.chunks_exact(4)) {
         // 'pivot' rgba is always BigEndian.
-        let color32 = reader.read_u32::<BigEndian>()? as u32;
-        rgb565.write_u16::<O>(rgba2rgb565(color32))?;
+        O::write_u16(dst, rgba2rgb565(BigEndian::read_u32(src)));
     }
     Ok(rgb565)
 }
 
 /// Converts Rgba565 with specified endianness byte buffer as RGBA.
 pub fn rgb565_to_rgba<B: ByteOrder>(data: &[u8], w: u32, h: u32) -> Result<Vec<u8>> {
+    let data = pixels_of(data, w, h, 2)?;
     // we'll expand the rgb565 into rgba; it'll take twice the space.
-    let pixels = (w * h) as usize;
-    let mut rgba = Vec::with_capacity(pixels * 4);
-    let ref mut data_reader = Cursor::new(data);
-    for _ in 0..pixels {
-        let color16 = data_reader.read_u16::<B>()?;
-        let color32 = rgb5652rgba(color16);
+    let mut rgba = vec![0; data.len() * 2];
+    for (dst, src) in rgba.chunks_exact_mut(4).zip(data.chunks_exact(2)) {
         // 'pivot' rgba is always BigEndian.
-        rgba.write_u32::<BigEndian>(color32)?;
+        BigEndian::write_u32(dst, rgb5652rgba(B::read_u16(src)));
     }
     Ok(rgba)
 }
@@ -201,3 +352,72 @@ fn test_i_do_not_mess_up_littleendian() {
     // red in rgba model full opacity (I want rgba to be a 'pivot' format, always in BigEndian).
     assert_eq!(0xF80000FF as u32, (&converted as &[u8]).read_u32::<BigEndian>().unwrap());
 }
+
+#[test]
+fn test_transforms_move_pixels() {
+    // a 3x2 raster, each pixel holds its index in the red channel.
+    //   0 1 2
+    //   3 4 5
+    let raster: Vec<u8> = (0..6).flat_map(|i| vec![i as u8, 0, 0, 0xFF]).collect();
+    let reds = |rgba: &[u8]| -> Vec<u8> { rgba.iter().step_by(4).cloned().collect() };
+    let expect = |transform: Transform, w: u32, h: u32, expected: [u8; 6]| {
+        let (transformed, tw, th) = transform.apply(&raster, 3, 2).unwrap();
+        assert_eq!((tw, th), (w, h), "{}", transform);
+        assert_eq!(reds(&transformed), expected.to_vec(), "{}", transform);
+    };
+    expect(Transform::Identity, 3, 2, [0, 1, 2, 3, 4, 5]);
+    expect(Transform::Rotate90, 2, 3, [3, 0, 4, 1, 5, 2]);
+    expect(Transform::Rotate180, 3, 2, [5, 4, 3, 2, 1, 0]);
+    expect(Transform::Rotate270, 2, 3, [2, 5, 1, 4, 0, 3]);
+    expect(Transform::MirrorH, 3, 2, [2, 1, 0, 5, 4, 3]);
+    expect(Transform::MirrorV, 3, 2, [3, 4, 5, 0, 1, 2]);
+}
+
+#[test]
+fn test_transforms_are_reversible() {
+    let raster: Vec<u8> = (0..(5 * 3 * 4)).map(|i| i as u8).collect();
+    for transform in Transform::enumerate() {
+        let (transformed, w, h) = transform.apply(&raster, 5, 3).unwrap();
+        let (reverted, ww, hh) = transform.inverse().apply(&transformed, w, h).unwrap();
+        assert_eq!((ww, hh), (5, 3));
+        assert_eq!(reverted, raster, "{} then {}", transform, transform.inverse());
+        assert_eq!(Transform::by_name(&format!("{}", transform)).unwrap(), *transform);
+    }
+}
+
+#[test]
+fn test_scale_nearest() {
+    // a 2x2 raster, each pixel holds its index in the red channel.
+    let raster: Vec<u8> = (0..4).flat_map(|i| vec![i as u8, 0, 0, 0xFF]).collect();
+    let reds = |rgba: &[u8]| -> Vec<u8> { rgba.iter().step_by(4).cloned().collect() };
+    assert_eq!(reds(&scale_nearest(&raster, 2, 2, 4, 2)), vec![0, 0, 1, 1, 2, 2, 3, 3]);
+    assert_eq!(reds(&scale_nearest(&raster, 2, 2, 1, 1)), vec![0]);
+}
+
+#[test]
+fn test_png_color_types_are_rgba() {
+    let encode = |color: png::ColorType, depth: png::BitDepth, data: &[u8], palette: Option<Vec<u8>>| {
+        let mut file = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut file, 2, 1);
+            encoder.set_color(color);
+            encoder.set_depth(depth);
+            if let Some(palette) = palette { encoder.set_palette(palette) };
+            encoder.write_header().unwrap().write_image_data(data).unwrap();
+        }
+        png_to_rgba(&file as &[u8]).unwrap()
+    };
+    let expected = (vec![0x10, 0x20, 0x30, 0xFF, 0x40, 0x50, 0x60, 0xFF], 2, 1);
+    assert_eq!(encode(png::ColorType::Rgb, png::BitDepth::Eight, &[0x10, 0x20, 0x30, 0x40, 0x50, 0x60], None), expected);
+    assert_eq!(encode(png::ColorType::Rgb, png::BitDepth::Sixteen,
+                      &[0x10, 1, 0x20, 2, 0x30, 3, 0x40, 4, 0x50, 5, 0x60, 6], None), expected);
+    assert_eq!(encode(png::ColorType::Indexed, png::BitDepth::Eight, &[1, 0],
+                      Some(vec![0x40, 0x50, 0x60, 0x10, 0x20, 0x30])), expected);
+    assert_eq!(encode(png::ColorType::Grayscale, png::BitDepth::Eight, &[0x10, 0x40], None),
+               (vec![0x10, 0x10, 0x10, 0xFF, 0x40, 0x40, 0x40, 0xFF], 2, 1));
+    // 2 bits grays are scaled to 8 bits.
+    assert_eq!(encode(png::ColorType::Grayscale, png::BitDepth::Two, &[0b1100_0000], None),
+               (vec![0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0xFF], 2, 1));
+    assert_eq!(encode(png::ColorType::GrayscaleAlpha, png::BitDepth::Eight, &[0x10, 0x80, 0x40, 0], None),
+               (vec![0x10, 0x10, 0x10, 0x80, 0x40, 0x40, 0x40, 0], 2, 1));
+}
@@ -1,14 +1,22 @@
 extern crate png;
 
-use std::io::{Read, Result};
+use std::fmt;
+use std::fmt::Display;
+use std::io::{Error as IOError, ErrorKind, Read, Result};
 use super::byteorder;
+use self::codec::Codec;
 
+pub mod codec;
+pub mod encoding;
+pub mod formats;
 pub mod image;
+pub mod stream;
 
 #[cfg(feature = "libflate")]
 pub mod z_lib_libflate;
 
-#[cfg(feature = "libflate")]
+// 'flate2' is preferred when both backends are compiled.
+#[cfg(all(feature = "libflate", not(feature = "flate2")))]
 pub use self::z_lib_libflate as z_lib;
 
 #[cfg(feature = "flate2")]
@@ -17,9 +25,96 @@ pub mod z_lib_flate2;
 #[cfg(feature = "flate2")]
 pub use self::z_lib_flate2 as z_lib;
 
+#[cfg(feature = "zopfli")]
+pub mod z_lib_zopfli;
+
 pub fn load_raw<R: Read>(mut reader: R) -> Result<(Vec<u8>)> {
     let mut buf = Vec::with_capacity(8192); // no idea how to size it.
     reader.read_to_end(&mut buf)?;
     Ok(buf)
 }
 
+
+/// Fastest compression level (no compression at all).
+pub const MIN_LEVEL: u32 = 0;
+/// Best compression level, it's the default since logo partitions are small.
+pub const MAX_LEVEL: u32 = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Deflate strategies, as known by zlib.
+pub enum Strategy {
+    /// Plain deflate.
+    Default,
+    /// Favours Huffman coding over string matching (only matches of 5 bytes or more).
+    Filtered,
+    /// Huffman coding only, no string matching.
+    HuffmanOnly,
+    /// Run-length encoding only (matches at distance 1).
+    Rle,
+    /// Fixed Huffman codes.
+    Fixed,
+}
+
+static SUPPORTED_STRATEGIES: [(Strategy, &str); 5] = [
+    (Strategy::Default, "default"),
+    (Strategy::Filtered, "filtered"),
+    (Strategy::HuffmanOnly, "huffman"),
+    (Strategy::Rle, "rle"),
+    (Strategy::Fixed, "fixed")];
+
+impl Strategy {
+    /// Lists all managed strategies.
+    pub fn enumerate<'a>() -> Vec<&'a Strategy> {
+        SUPPORTED_STRATEGIES.iter().map(|(strategy, _name)| strategy).collect()
+    }
+
+    pub fn by_name(name: &str) -> Result<Strategy> {
+        SUPPORTED_STRATEGIES.iter()
+            .find(|(_, n)| name.eq(*n))
+            .map(|(strategy, _)| *strategy)
+            .ok_or_else(|| IOError::new(
+                ErrorKind::InvalidInput, format!("{} is not a compression strategy", name)))
+    }
+}
+
+impl Display for Strategy {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match SUPPORTED_STRATEGIES.iter().find(|(strategy, _)| strategy.eq(self)) {
+            Some((_, name)) => fmt.write_str(name),
+            None => fmt.write_str("Strategy?") // should be unreachable.
+        }
+    }
+}
+
+/// Checks a compression level is within bounds.
+pub fn check_level(level: u32) -> Result<u32> {
+    if level > MAX_LEVEL {
+        Err(IOError::new(ErrorKind::InvalidInput,
+                         format!("compression level {} is not within {}..{}", level, MIN_LEVEL, MAX_LEVEL)))
+    } else {
+        Ok(level)
+    }
+}
+
+/// Tries every level and strategy of a backend until deflating `data` gives back the `original` compressed bytes.
+/// This is how we find out the settings used by the vendor, so that an untouched image
+/// is repacked byte for byte.
+pub fn find_level(data: &[u8], original: &[u8], backend: &dyn Codec) -> Option<(u32, Strategy)> {
+    // a backend without settings has a single try.
+    if !backend.has_settings() {
+        return match backend.deflate(data) {
+            Ok(ref deflated) if deflated[..] == original[..] => Some((backend.level(), Strategy::Default)),
+            _ => None,
+        };
+    }
+    // vendors most likely use the default strategy, and a high level.
+    for strategy in Strategy::enumerate() {
+        for level in (MIN_LEVEL..=MAX_LEVEL).rev() {
+            match codec::by_name(backend.name(), level, *strategy).and_then(|probe| probe.deflate(data)) {
+                Ok(ref deflated) if deflated[..] == original[..] => return Some((level, *strategy)),
+                _ => (),
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,62 @@
+use std::io::{self, Error as IOError, ErrorKind, Read, Result, Write};
+use super::codec::Codec;
+use super::encoding::Encoding;
+use super::image::{self, ImageIO};
+use super::png;
+use super::super::ColorMode;
+
+// Row streaming between PNG files and slots: rows are converted one at a time, so that memory
+// does not grow with the image size. That's for big logos on small devices.
+// Streams only go straight: transforms which move pixels across rows need the whole image.
+
+/// Reads a PNG row by row, converts each row to the device color mode and encodes it at once.
+/// Returns the blob, with the width and height of the image.
+/// Interlaced PNG files cannot be streamed, they fail with `ErrorKind::Unsupported`.
+pub fn png_to_blob<R: Read>(reader: R, color_mode: &ColorMode, strip_alpha: bool,
+                            encoding: Encoding, codec: &dyn Codec) -> Result<(Vec<u8>, u32, u32)> {
+    let mut decoder = png::Decoder::new(reader);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut png_reader = decoder.read_info()?;
+    let (w, h, interlaced) = {
+        let info = png_reader.info();
+        (info.width, info.height, info.interlaced)
+    };
+    if interlaced {
+        return Err(IOError::new(ErrorKind::Unsupported, "cannot stream an interlaced PNG"));
+    }
+    let (color_type, bit_depth) = png_reader.output_color_type();
+    let mut sink = encoding.encoder(codec)?;
+    while let Some(row) = png_reader.next_row()? {
+        let mut rgba = image::png_pixels_to_rgba(row.data(), color_type, bit_depth)?;
+        if strip_alpha { image::strip_alpha(&mut rgba) };
+        sink.write_all(&color_mode.rgba_to_device(&rgba, w, 1)?)?;
+    }
+    Ok((sink.finish()?, w, h))
+}
+
+/// Detects the encoding of a blob, and tells how many bytes it decodes to without keeping them.
+/// Just like `encoding::decode`, a blob which cannot be decoded as it looks is taken as raw.
+pub fn measure(blob: &[u8], codec: &dyn Codec) -> (Encoding, u64) {
+    let encoding = Encoding::detect(blob);
+    let decoded = encoding.decoder(blob, codec)
+        .and_then(|mut decoder| io::copy(&mut decoder, &mut io::sink()));
+    match decoded {
+        Ok(len) => (encoding, len),
+        Err(_) => (Encoding::Raw, blob.len() as u64),
+    }
+}
+
+/// Decodes a blob row by row, converts each row to RGBA and writes it to a PNG at once.
+pub fn blob_to_png<W: Write>(writer: W, blob: &[u8], encoding: Encoding, codec: &dyn Codec,
+                             color_mode: &ColorMode, w: u32, h: u32) -> Result<()> {
+    let mut decoder = encoding.decoder(blob, codec)?;
+    let mut png_writer = image::rgba_png_encoder(writer, w, h).write_header()?;
+    let mut stream = png_writer.stream_writer()?;
+    let mut row = vec![0; (w * color_mode.bytes_per_pixel()) as usize];
+    for _ in 0..h {
+        decoder.read_exact(&mut row)?;
+        stream.write_all(&color_mode.device_to_rgba(&row, w, 1)?)?;
+    }
+    stream.finish()?;
+    Ok(())
+}
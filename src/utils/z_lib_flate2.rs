@@ -1,11 +1,17 @@
 extern crate flate2;
+extern crate miniz_oxide;
 
-use std::io::{Read, Result, Write};
+use std::io::{Error as IOError, Read, Result, Write};
 use self::flate2::Compression;
 use self::flate2::write::ZlibEncoder;
-use self::flate2::read::ZlibDecoder;
+use self::flate2::read::{GzDecoder, ZlibDecoder};
+use self::miniz_oxide::deflate::core::{
+    compress, create_comp_flags_from_zip_params, CompressionStrategy, CompressorOxide, TDEFLFlush, TDEFLStatus};
+use super::{check_level, Strategy};
+use super::codec::{Buffered, Codec, Sink};
 
 // It's just a thin wrapper around 'flate2'.
+// 'flate2' does not expose zlib strategies, so we turn to its rust backend 'miniz_oxide' for them.
 
 pub fn inflate(data: &[u8]) -> Result<Vec<u8>> {
     let mut decoder = ZlibDecoder::new(data);
@@ -13,8 +19,79 @@ pub fn inflate(data: &[u8]) -> Result<Vec<u8>> {
     decoder.read_to_end(&mut uncompressed).map(|_sz| uncompressed)
 }
 
-pub fn deflate(data: &[u8]) -> Result<Vec<u8>> {
-    let mut e = ZlibEncoder::new(Vec::new(), Compression::best());
-    e.write_all(data)?;
-    e.finish()
-}
\ No newline at end of file
+pub fn inflate_stream(data: &[u8]) -> Box<dyn Read + '_> {
+    Box::new(ZlibDecoder::new(data))
+}
+
+pub fn gunzip(data: &[u8]) -> Result<Vec<u8>> {
+    let mut decoder = GzDecoder::new(data);
+    let mut uncompressed = Vec::new();
+    decoder.read_to_end(&mut uncompressed).map(|_sz| uncompressed)
+}
+
+pub fn deflate(data: &[u8], level: u32, strategy: Strategy) -> Result<Vec<u8>> {
+    let level = check_level(level)?;
+    let strategy = match strategy {
+        Strategy::Default => {
+            let mut e = ZlibEncoder::new(Vec::new(), Compression::new(level));
+            e.write_all(data)?;
+            return e.finish();
+        }
+        Strategy::Filtered => CompressionStrategy::Filtered,
+        Strategy::HuffmanOnly => CompressionStrategy::HuffmanOnly,
+        Strategy::Rle => CompressionStrategy::RLE,
+        Strategy::Fixed => CompressionStrategy::Fixed,
+    };
+    // positive window bits means a zlib header.
+    let flags = create_comp_flags_from_zip_params(level as i32, 1, strategy as i32);
+    let mut compressor = CompressorOxide::new(flags);
+    let mut input = data;
+    let mut output = vec![0; data.len() / 2 + 64];
+    let mut out_pos = 0;
+    loop {
+        let (status, bytes_in, bytes_out) =
+            compress(&mut compressor, input, &mut output[out_pos..], TDEFLFlush::Finish);
+        out_pos += bytes_out;
+        input = &input[bytes_in..];
+        match status {
+            TDEFLStatus::Done => {
+                output.truncate(out_pos);
+                return Ok(output);
+            }
+            TDEFLStatus::Okay => {
+                // needs more room.
+                let grown = output.len() * 2;
+                output.resize(grown, 0);
+            }
+            _ => return Err(IOError::other(format!("deflate failed ({:?})", status))),
+        }
+    }
+}
+
+/// 'flate2' as a compression backend.
+pub struct Flate2Codec {
+    pub level: u32,
+    pub strategy: Strategy,
+}
+
+impl Codec for Flate2Codec {
+    fn name(&self) -> &'static str { "flate2" }
+    fn level(&self) -> u32 { self.level }
+    fn inflate(&self, data: &[u8]) -> Result<Vec<u8>> { inflate(data) }
+    fn deflate(&self, data: &[u8]) -> Result<Vec<u8>> { deflate(data, self.level, self.strategy) }
+    fn gunzip(&self, data: &[u8]) -> Result<Vec<u8>> { gunzip(data) }
+    fn inflate_stream<'a>(&self, data: &'a [u8]) -> Result<Box<dyn Read + 'a>> { Ok(inflate_stream(data)) }
+    /// Only the default strategy streams, 'miniz_oxide' strategies are buffered.
+    fn deflate_stream<'a>(&'a self) -> Result<Box<dyn Sink + 'a>> {
+        Ok(match self.strategy {
+            Strategy::Default => Box::new(ZlibEncoder::new(Vec::new(), Compression::new(self.level))),
+            _ => Box::new(Buffered::new(move |data: &[u8]| self.deflate(data))),
+        })
+    }
+}
+
+impl Sink for ZlibEncoder<Vec<u8>> {
+    fn finish(self: Box<Self>) -> Result<Vec<u8>> {
+        ZlibEncoder::finish(*self)
+    }
+}
@@ -1,20 +1,71 @@
 extern crate libflate;
 
-use self::libflate::zlib::{Decoder, Encoder};
-use std::io::{Read, Result, Write};
+use self::libflate::lz77::NoCompressionLz77Encoder;
+use self::libflate::gzip;
+use self::libflate::zlib::{Decoder, EncodeOptions, Encoder};
+use std::io::{Error as IOError, ErrorKind, Read, Result, Write};
+use super::{check_level, Strategy};
+use super::codec::Codec;
 
 // It's just a thin wrapper around 'libflate'.
 // If you want a "pure rust" program (and don't require high compression) this is the library to use.
 
-pub fn inflate<R>(data: R) -> Result<Vec<u8>> where
-    R: Read{
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>> {
     let mut decoder = Decoder::new(data)?;
     let mut uncompressed = Vec::new();
     decoder.read_to_end(&mut uncompressed).map(|_sz| uncompressed)
 }
 
-pub fn deflate(data: &[u8]) -> Result<Vec<u8>> {
-    let mut encoder = Encoder::new(Vec::new())?;
-    encoder.write_all(&data[..])?;
-    encoder.finish().into_result()
+pub fn inflate_stream(data: &[u8]) -> Result<Box<dyn Read + '_>> {
+    Ok(Box::new(Decoder::new(data)?))
+}
+
+pub fn gunzip(data: &[u8]) -> Result<Vec<u8>> {
+    let mut decoder = gzip::Decoder::new(data)?;
+    let mut uncompressed = Vec::new();
+    decoder.read_to_end(&mut uncompressed).map(|_sz| uncompressed)
+}
+
+/// 'libflate' has no notion of level: 0 stores data, any other level compresses.
+/// It does not support the 'filtered' and 'rle' strategies.
+pub fn deflate(data: &[u8], level: u32, strategy: Strategy) -> Result<Vec<u8>> {
+    let level = check_level(level)?;
+    match strategy {
+        Strategy::HuffmanOnly if level > 0 => {
+            // literals only, still Huffman encoded.
+            let options = EncodeOptions::with_lz77(NoCompressionLz77Encoder::new());
+            let mut encoder = Encoder::with_options(Vec::new(), options)?;
+            encoder.write_all(data)?;
+            encoder.finish().into_result()
+        }
+        Strategy::Default | Strategy::Fixed | Strategy::HuffmanOnly => {
+            let mut options = EncodeOptions::new();
+            if level == 0 {
+                options = options.no_compression();
+            } else if strategy == Strategy::Fixed {
+                options = options.fixed_huffman_codes();
+            }
+            let mut encoder = Encoder::with_options(Vec::new(), options)?;
+            encoder.write_all(data)?;
+            encoder.finish().into_result()
+        }
+        _ => Err(IOError::new(ErrorKind::InvalidInput,
+                              format!("'{}' strategy is not supported by libflate", strategy))),
+    }
+}
+
+/// 'libflate' as a compression backend.
+pub struct LibflateCodec {
+    pub level: u32,
+    pub strategy: Strategy,
+}
+
+impl Codec for LibflateCodec {
+    fn name(&self) -> &'static str { "libflate" }
+    fn level(&self) -> u32 { self.level }
+    fn inflate(&self, data: &[u8]) -> Result<Vec<u8>> { inflate(data) }
+    fn deflate(&self, data: &[u8]) -> Result<Vec<u8>> { deflate(data, self.level, self.strategy) }
+    fn gunzip(&self, data: &[u8]) -> Result<Vec<u8>> { gunzip(data) }
+    fn inflate_stream<'a>(&self, data: &'a [u8]) -> Result<Box<dyn Read + 'a>> { inflate_stream(data) }
+    // no `deflate_stream`: libflate's blocks follow the writes, streamed rows would not give the bytes of `deflate`.
 }
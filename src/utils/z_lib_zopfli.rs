@@ -0,0 +1,41 @@
+extern crate zopfli;
+
+use std::io::{Read, Result};
+use std::num::NonZeroU64;
+use self::zopfli::{Format, Options};
+use super::codec::Codec;
+use super::{z_lib, MAX_LEVEL};
+
+// It's just a thin wrapper around 'zopfli'.
+// Zopfli only compresses: it's very slow, but squeezes a few more percents than zlib's best level,
+// and it still writes standard zlib streams that any inflater (including MTK's bootloader) reads.
+
+/// Above this size, zopfli recommends fewer iterations or it gets much too slow.
+const LARGE_DATA: usize = 1 << 20;
+
+pub fn deflate(data: &[u8]) -> Result<Vec<u8>> {
+    let iterations = if data.len() > LARGE_DATA { 5 } else { 15 };
+    let options = Options {
+        iteration_count: NonZeroU64::new(iterations).unwrap(),
+        ..Options::default()
+    };
+    let mut deflated = Vec::new();
+    zopfli::compress(options, Format::Zlib, data, &mut deflated)?;
+    Ok(deflated)
+}
+
+/// 'zopfli' as a compression backend, it relies on the default backend to inflate.
+/// zlib and gzip streams hold the same deflated data, so zopfli's zlib stream is also rewrapped as gzip.
+pub struct ZopfliCodec;
+
+impl Codec for ZopfliCodec {
+    fn name(&self) -> &'static str { "zopfli" }
+    fn level(&self) -> u32 { MAX_LEVEL }
+    fn has_settings(&self) -> bool { false }
+    fn inflate(&self, data: &[u8]) -> Result<Vec<u8>> { z_lib::inflate(data) }
+    fn deflate(&self, data: &[u8]) -> Result<Vec<u8>> { deflate(data) }
+    fn gunzip(&self, data: &[u8]) -> Result<Vec<u8>> { z_lib::gunzip(data) }
+    fn inflate_stream<'a>(&self, data: &'a [u8]) -> Result<Box<dyn Read + 'a>> {
+        super::codec::inflater()?.inflate_stream(data)
+    }
+}
@@ -0,0 +1,69 @@
+extern crate mtklogo;
+
+use mtklogo::ColorMode;
+use mtklogo::utils::image::ImageIO;
+
+/// Two 'pivot' RGBA pixels: |r|g|b|a| each.
+const RGBA: [u8; 8] = [0x11, 0x22, 0x33, 0x44, 0xA0, 0xB0, 0xC0, 0xD0];
+
+/// Two pixels rgb565 represents exactly (low bits are zeros, opaque).
+const RGBA_565: [u8; 8] = [0xF8, 0x04, 0x08, 0xFF, 0x08, 0xFC, 0xF8, 0xFF];
+
+/// What the device holds for these pixels, in each mode, written by hand.
+fn layout(mode: &str) -> (&'static [u8], &'static [u8]) {
+    match mode {
+        "rgbabe" => (&RGBA, &[0x11, 0x22, 0x33, 0x44, 0xA0, 0xB0, 0xC0, 0xD0]),
+        "rgbale" => (&RGBA, &[0x44, 0x33, 0x22, 0x11, 0xD0, 0xC0, 0xB0, 0xA0]),
+        "bgrabe" => (&RGBA, &[0x33, 0x22, 0x11, 0x44, 0xC0, 0xB0, 0xA0, 0xD0]),
+        "bgrale" => (&RGBA, &[0x44, 0x11, 0x22, 0x33, 0xD0, 0xA0, 0xB0, 0xC0]),
+        // 0xF821 = 11111|000001|00001 and 0x0FFF = 00001|111111|11111.
+        "rgb565be" => (&RGBA_565, &[0xF8, 0x21, 0x0F, 0xFF]),
+        "rgb565le" => (&RGBA_565, &[0x21, 0xF8, 0xFF, 0x0F]),
+        _ => panic!("no hand-written layout for {}", mode),
+    }
+}
+
+#[test]
+fn every_mode_matches_its_layout() {
+    for mode in ColorMode::enumerate() {
+        let name = format!("{}", mode);
+        let (rgba, device) = layout(&name);
+        assert_eq!(device.len() as u32, 2 * mode.bytes_per_pixel(), "{}", name);
+        assert_eq!(mode.rgba_to_device(rgba, 2, 1).unwrap(), device, "{} encoding", name);
+        assert_eq!(mode.device_to_rgba(device, 2, 1).unwrap(), rgba, "{} decoding", name);
+        // 1x2 is the same two pixels.
+        assert_eq!(mode.device_to_rgba(device, 1, 2).unwrap(), rgba, "{} decoding", name);
+    }
+}
+
+#[test]
+fn every_mode_round_trips() {
+    // all values of every channel.
+    let rgba: Vec<u8> = (0..256 * 4).map(|i| (i / 4 + i % 4 * 64) as u8).collect();
+    for mode in ColorMode::enumerate() {
+        let device = mode.rgba_to_device(&rgba, 16, 16).unwrap();
+        assert_eq!(device.len() as u32, 256 * mode.bytes_per_pixel(), "{}", mode);
+        let again = mode.device_to_rgba(&device, 16, 16).unwrap();
+        if mode.bytes_per_pixel() == 4 {
+            assert_eq!(again, rgba, "{}", mode);
+        } else {
+            // rgb565 keeps high bits, and is opaque.
+            let expected: Vec<u8> = rgba.chunks(4)
+                .flat_map(|p| vec![p[0] & 0xF8, p[1] & 0xFC, p[2] & 0xF8, 0xFF])
+                .collect();
+            assert_eq!(again, expected, "{}", mode);
+            // and that's stable.
+            assert_eq!(mode.rgba_to_device(&again, 16, 16).unwrap(), device, "{}", mode);
+        }
+    }
+}
+
+#[test]
+fn short_rasters_are_errors() {
+    for mode in ColorMode::enumerate() {
+        let (rgba, device) = layout(&format!("{}", mode));
+        assert!(mode.rgba_to_device(&rgba[..7], 2, 1).is_err(), "{}", mode);
+        assert!(mode.device_to_rgba(&device[..device.len() - 1], 2, 1).is_err(), "{}", mode);
+        assert!(mode.device_to_rgba(device, 3, 1).is_err(), "{}", mode);
+    }
+}
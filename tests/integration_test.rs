@@ -8,8 +8,9 @@ extern crate mtklogo;
 // - if I'm not too bad with image formats...
 
 use byteorder::{ByteOrder, LittleEndian, BigEndian, ReadBytesExt};
-use mtklogo::{LogoImage};
-use mtklogo::utils::{image, load_raw};
+use mtklogo::{ColorMode, LogoImage};
+use mtklogo::utils::{codec, encoding, encoding::Encoding, find_level, image, image::ImageIO, load_raw,
+                     stream, Strategy, MAX_LEVEL};
 use mtklogo::utils::z_lib;
 use std::fs::File;
 use std::io::{BufWriter, Result, Write, Read};
@@ -48,7 +49,7 @@ fn png_to_raster_z(png: &[u8]) -> Vec<u8> {
     // Encodes it as RGBA.
     let (rgba, _, _) = image::png_to_rgba(png).unwrap();
     // zips it
-    z_lib::deflate(&rgba).unwrap()
+    z_lib::deflate(&rgba, MAX_LEVEL, Strategy::Default).unwrap()
 }
 
 // just to avoid loading the samples too many times...
@@ -78,7 +79,7 @@ fn dither_rgb565() {
         // Gets an RGBA image
         let (rgba, w, h) = image::png_to_rgba(&IMAGE1_PNG as &[u8]).unwrap();
         // encodes it as rgb565
-        let rgba565 = image::rgba_to_rgb565::<O,_>(&rgba as &[u8], w, h).unwrap();
+        let rgba565 = image::rgba_to_rgb565::<O>(&rgba, w, h).unwrap();
         // It must be halved in size.
         assert_eq!(rgba.len() / 2, rgba565.len());
         // encodes it again as rgb
@@ -118,7 +119,7 @@ fn zlib_is_quite_symetric() {
     let blob1 = &IMAGE1_Z;
     // decompresses it
     let decompressed = z_lib::inflate(blob1 as &[u8]).unwrap();
-    let recompressed = z_lib::deflate(&decompressed).unwrap();
+    let recompressed = z_lib::deflate(&decompressed, MAX_LEVEL, Strategy::Default).unwrap();
     let grow_ratio = (100 * (recompressed.len() - blob1.len())) / blob1.len();
     println!("{} - {} - {}%", blob1.len(), recompressed.len(), grow_ratio);
     #[cfg(feature = "flate2")]
@@ -135,6 +136,102 @@ fn zlib_is_quite_symetric() {
     more_tests();
 }
 
+/// Every level and strategy must give back the same data, and we can tell which one was used.
+#[test]
+fn zlib_levels_and_strategies() {
+    let (rgba, _, _) = image::png_to_rgba(&IMAGE2_PNG as &[u8]).unwrap();
+    // a small chunk of the image is enough, and much faster.
+    let data = &rgba[..16384];
+    for strategy in Strategy::enumerate() {
+        for level in 0..(MAX_LEVEL + 1) {
+            match z_lib::deflate(data, level, *strategy) {
+                Ok(deflated) =>
+                    assert_eq!(data, &z_lib::inflate(&deflated as &[u8]).unwrap() as &[u8]),
+                // some backends do not support every strategy.
+                Err(e) => println!("{} level {}: {}", strategy, level, e),
+            }
+        }
+    }
+    let backend = codec::default_codec(MAX_LEVEL, Strategy::Default).unwrap();
+    for &(level, strategy) in [(MAX_LEVEL, Strategy::Default), (3, Strategy::Default), (1, Strategy::Fixed)].iter() {
+        let deflated = z_lib::deflate(data, level, strategy).unwrap();
+        let (found_level, found_strategy) = find_level(data, &deflated, backend.as_ref()).unwrap();
+        // several settings may give the same bytes, it's fine as long as they do.
+        assert_eq!(deflated, z_lib::deflate(data, found_level, found_strategy).unwrap());
+    }
+    assert!(z_lib::deflate(data, MAX_LEVEL + 1, Strategy::Default).is_err());
+    assert_eq!(find_level(data, b"certainly not a zlib stream", backend.as_ref()), None);
+}
+
+/// Every backend compiled in must read what the others write, through the logo image.
+#[test]
+fn codecs_are_interchangeable() {
+    let (rgba, _, _) = image::png_to_rgba(&IMAGE2_PNG as &[u8]).unwrap();
+    let rasters = vec![rgba[..4096].to_vec(), rgba[4096..6000].to_vec()];
+    assert!(!codec::names().is_empty());
+    for writer in codec::names() {
+        let codec = codec::by_name(writer, MAX_LEVEL, Strategy::Default).unwrap();
+        assert_eq!(codec.name(), writer);
+        let logo = LogoImage::encode(&rasters, codec.as_ref()).unwrap();
+        assert_eq!(logo.table.logo_count, 2);
+        for reader in codec::names() {
+            let codec = codec::by_name(reader, 0, Strategy::Default).unwrap();
+            assert_eq!(rasters, logo.decode(codec.as_ref()).unwrap());
+        }
+    }
+    assert!(codec::by_name("lzma", MAX_LEVEL, Strategy::Default).is_err());
+    assert!(codec::by_name(codec::names()[0], MAX_LEVEL + 1, Strategy::Default).is_err());
+}
+
+/// Every encoding must give back the raster, and be told apart from the others.
+#[test]
+fn encodings_round_trip() {
+    let (rgba, _, _) = image::png_to_rgba(&IMAGE2_PNG as &[u8]).unwrap();
+    let raster = &rgba[..4096];
+    for name in codec::names() {
+        let codec = codec::by_name(name, MAX_LEVEL, Strategy::Default).unwrap();
+        let mut blobs = Vec::new();
+        for encoding in Encoding::enumerate() {
+            let blob = encoding.encode(raster, codec.as_ref()).unwrap();
+            assert_eq!(Encoding::detect(&blob), *encoding, "{} with {}", encoding, name);
+            assert_eq!(encoding::decode(&blob, codec.as_ref()), (*encoding, raster.to_vec()));
+            blobs.push(blob);
+        }
+        // a logo image may mix encodings.
+        let logo = LogoImage::new_blobs(blobs);
+        for decoded in logo.decode(codec.as_ref()).unwrap() {
+            assert_eq!(raster, &decoded as &[u8]);
+        }
+    }
+    // a broken stream is taken as a raw framebuffer.
+    let broken = [0x78, 0x9C, 0xFF, 0xFF];
+    let inflater = codec::inflater().unwrap();
+    assert_eq!(encoding::decode(&broken, inflater.as_ref()), (Encoding::Raw, broken.to_vec()));
+}
+
+/// Streaming rows must give the very same blobs and images as working on whole rasters.
+#[test]
+fn streams_match_buffers() {
+    let (rgba, w, h) = image::png_to_rgba(&IMAGE1_PNG as &[u8]).unwrap();
+    let color_mode = ColorMode::by_name("rgb565le").unwrap();
+    let device = color_mode.rgba_to_device(&rgba, w, h).unwrap();
+    let codec = codec::default_codec(6, Strategy::Default).unwrap();
+    for encoding in Encoding::enumerate() {
+        let (blob, ww, hh) = stream::png_to_blob(&IMAGE1_PNG as &[u8], color_mode, false,
+                                                 *encoding, codec.as_ref()).unwrap();
+        assert_eq!((ww, hh), (w, h));
+        assert_eq!(blob, encoding.encode(&device, codec.as_ref()).unwrap(), "{}", encoding);
+        assert_eq!(stream::measure(&blob, codec.as_ref()), (*encoding, device.len() as u64));
+        // and back to a PNG.
+        let mut png_data = Vec::new();
+        stream::blob_to_png(&mut png_data, &blob, *encoding, codec.as_ref(), color_mode, w, h).unwrap();
+        let (streamed, _, _) = image::png_to_rgba(&png_data as &[u8]).unwrap();
+        assert_eq!(streamed, color_mode.device_to_rgba(&device, w, h).unwrap());
+    }
+    // a slot too short for the image is an error, not a truncated PNG.
+    let short = &device[..device.len() / 2];
+    assert!(stream::blob_to_png(Vec::new(), short, Encoding::Raw, codec.as_ref(), color_mode, w, h).is_err());
+}
 
 /// We check that converting from raster to PNG back and forth does not change a single bit!
 #[test]
@@ -161,3 +258,15 @@ fn png_is_not_lossy() {
     // Hopefully: decode(encode(x)) = x...
     assert!(&raster.eq(&raster_again));
 }
+
+/// Zopfli must write standard zlib streams, and beat zlib's best level.
+#[cfg(feature = "zopfli")]
+#[test]
+fn zopfli_is_readable_and_smaller() {
+    use mtklogo::utils::z_lib_zopfli;
+    let (rgba, _, _) = image::png_to_rgba(&IMAGE2_PNG as &[u8]).unwrap();
+    let data = &rgba[..4096];
+    let deflated = z_lib_zopfli::deflate(data).unwrap();
+    assert_eq!(data, &z_lib::inflate(&deflated as &[u8]).unwrap() as &[u8]);
+    assert!(deflated.len() <= z_lib::deflate(data, MAX_LEVEL, Strategy::Default).unwrap().len());
+}